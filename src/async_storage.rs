@@ -0,0 +1,516 @@
+use std::{
+    borrow::Cow,
+    future::{ready, Future},
+    ops::Bound,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+
+use crate::{
+    storage::{encode_bound, prefix_range, KeyRange},
+    BackendError, BatchOp, Encodable, IterableStorage, KeyEncoding, KeyResult, KeySerializeError,
+    KeyType, KvResult, Order, RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+fn encode_bounds<K: Encodable<KeyEncoding>>(
+    low: Bound<K>,
+    high: Bound<K>,
+) -> Result<KeyRange, KeySerializeError> {
+    Ok((encode_bound!(low), encode_bound!(high)))
+}
+
+/// Future returning the next item of a stream.
+///
+/// Unlike an `async fn`, this is `Send` whenever the stream is, which the
+/// compiler cannot prove for streams borrowing from generic storage.
+pub(crate) struct Next<'a, S: ?Sized> {
+    stream: Pin<&'a mut S>,
+}
+
+pub(crate) fn next<S: Stream + ?Sized>(stream: Pin<&mut S>) -> Next<'_, S> {
+    Next { stream }
+}
+
+impl<S: Stream + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.stream.as_mut().poll_next(cx)
+    }
+}
+
+/// Async counterpart of [`Storage`], for backends reached over the network or
+/// other async I/O.
+///
+/// Futures are `Send`, so that operations can be spawned onto multi-threaded
+/// runtimes. Keys are encoded before the returned future is created, so key
+/// types need not be `Send`.
+pub trait AsyncStorage: Send + Sync {
+    fn get<K: Encodable<KeyEncoding>>(
+        &self,
+        key: &K,
+    ) -> impl Future<Output = Result<Option<Cow<'_, [u8]>>, RawStorageError>> + Send {
+        let key = key.encode();
+        async move { Ok(self.get_raw(&key?).await?) }
+    }
+
+    fn get_raw(
+        &self,
+        key: &[u8],
+    ) -> impl Future<Output = Result<Option<Cow<'_, [u8]>>, BackendError>> + Send;
+}
+
+/// Async counterpart of [`StorageMut`].
+pub trait AsyncStorageMut: AsyncStorage {
+    fn set<K: Encodable<KeyEncoding>>(
+        &mut self,
+        key: &K,
+        value: Vec<u8>,
+    ) -> impl Future<Output = Result<(), RawStorageError>> + Send {
+        let key = key.encode();
+        async move { Ok(self.set_raw(key?, value).await?) }
+    }
+    fn set_raw(
+        &mut self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send;
+
+    fn delete<K: Encodable<KeyEncoding>>(
+        &mut self,
+        key: &K,
+    ) -> impl Future<Output = Result<(), RawStorageError>> + Send {
+        let key = key.encode();
+        async move { Ok(self.delete_raw(&key?).await?) }
+    }
+    fn delete_raw(&mut self, key: &[u8]) -> impl Future<Output = Result<(), BackendError>> + Send;
+
+    fn delete_range<K: Encodable<KeyEncoding>>(
+        &mut self,
+        low: Bound<K>,
+        high: Bound<K>,
+    ) -> impl Future<Output = Result<(), RawStorageError>> + Send {
+        let bounds = encode_bounds(low, high);
+        async move {
+            let (low, high) = bounds?;
+            Ok(self.delete_range_raw(low, high).await?)
+        }
+    }
+    /// See [`StorageMut::delete_range_raw`].
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send;
+
+    /// See [`StorageMut::write_batch`]. The default applies each write in turn.
+    fn write_batch(
+        &mut self,
+        batch: WriteBatch,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send {
+        async move {
+            for op in batch {
+                match op {
+                    BatchOp::Put(key, value) => self.set_raw(key, value).await?,
+                    BatchOp::Delete(key) => self.delete_raw(&key).await?,
+                    BatchOp::DeleteRange(low, high) => self.delete_range_raw(low, high).await?,
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Async counterpart of [`IterableStorage`], whose scans are [`Stream`]s.
+///
+/// Creating a stream does not touch the backend, so every backend failure,
+/// including one opening the scan, is yielded by the stream. Streams should not
+/// be polled further after yielding an error.
+pub trait AsyncIterableStorage: AsyncStorage {
+    type Keys<'a>: Stream<Item = KeyResult<'a>> + Send
+    where
+        Self: 'a;
+    type Iter<'a>: Stream<Item = KvResult<'a>> + Send
+    where
+        Self: 'a;
+
+    fn keys_raw(&self, low: Bound<Vec<u8>>, high: Bound<Vec<u8>>, order: Order) -> Self::Keys<'_>;
+
+    fn iter_raw(&self, low: Bound<Vec<u8>>, high: Bound<Vec<u8>>, order: Order) -> Self::Iter<'_>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, KeySerializeError> {
+        let (low, high) = encode_bounds(low, high)?;
+        Ok(self.keys_raw(low, high, order))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, KeySerializeError> {
+        let (low, high) = encode_bounds(low, high)?;
+        Ok(self.iter_raw(low, high, order))
+    }
+
+    /// See [`IterableStorage::prefix_keys`].
+    fn prefix_keys(&self, prefix: &[u8], order: Order) -> Self::Keys<'_> {
+        let (low, high) = prefix_range(prefix);
+        self.keys_raw(low, high, order)
+    }
+
+    /// See [`IterableStorage::prefix_iter`].
+    fn prefix_iter(&self, prefix: &[u8], order: Order) -> Self::Iter<'_> {
+        let (low, high) = prefix_range(prefix);
+        self.iter_raw(low, high, order)
+    }
+}
+
+/// Adapter implementing the async storage traits for a synchronous backend.
+///
+/// Every operation runs to completion on the calling task, blocking its
+/// executor thread for as long as the backend takes. This suits in-memory and
+/// other fast backends; slow ones belong on a blocking thread pool instead.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BlockingStorage<S> {
+    inner: S,
+}
+
+impl<S> BlockingStorage<S> {
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Storage + Send + Sync> AsyncStorage for BlockingStorage<S> {
+    fn get_raw(
+        &self,
+        key: &[u8],
+    ) -> impl Future<Output = Result<Option<Cow<'_, [u8]>>, BackendError>> + Send {
+        ready(self.inner.get_raw(key))
+    }
+}
+
+impl<S: StorageMut + Send + Sync> AsyncStorageMut for BlockingStorage<S> {
+    fn set_raw(
+        &mut self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send {
+        ready(self.inner.set_raw(key, value))
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> impl Future<Output = Result<(), BackendError>> + Send {
+        ready(self.inner.delete_raw(key))
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send {
+        ready(self.inner.delete_range_raw(low, high))
+    }
+
+    fn write_batch(
+        &mut self,
+        batch: WriteBatch,
+    ) -> impl Future<Output = Result<(), BackendError>> + Send {
+        ready(self.inner.write_batch(batch))
+    }
+}
+
+/// Stream over a scan of a [`BlockingStorage`], yielding the items of the
+/// backend's iterator, or the error opening it.
+pub struct BlockingStream<I> {
+    iter: Result<I, Option<BackendError>>,
+}
+
+impl<I> BlockingStream<I> {
+    fn new(iter: Result<I, RawStorageError>) -> Self {
+        let iter = match iter {
+            Ok(iter) => Ok(iter),
+            Err(RawStorageError::KeySerialize(e)) => match e {},
+            Err(RawStorageError::Backend(e)) => Err(Some(e)),
+        };
+        Self { iter }
+    }
+}
+
+// The iterator is never pinned, so moving it is fine
+impl<I> Unpin for BlockingStream<I> {}
+
+impl<T, I: Iterator<Item = Result<T, BackendError>>> Stream for BlockingStream<I> {
+    type Item = I::Item;
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(match &mut self.get_mut().iter {
+            Ok(iter) => iter.next(),
+            Err(e) => e.take().map(Err),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.iter {
+            Ok(iter) => iter.size_hint(),
+            Err(e) => (e.is_some() as usize, Some(e.is_some() as usize)),
+        }
+    }
+}
+
+impl<S> AsyncIterableStorage for BlockingStorage<S>
+where
+    S: IterableStorage + Send + Sync,
+    for<'a> S::Keys<'a>: Send,
+    for<'a> S::Iter<'a>: Send,
+{
+    type Keys<'a>
+        = BlockingStream<S::Keys<'a>>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = BlockingStream<S::Iter<'a>>
+    where
+        Self: 'a;
+
+    fn keys_raw(&self, low: Bound<Vec<u8>>, high: Bound<Vec<u8>>, order: Order) -> Self::Keys<'_> {
+        let keys = self
+            .inner
+            .keys(low.map(KeyType::<()>::Raw), high.map(KeyType::Raw), order);
+        BlockingStream::new(keys)
+    }
+
+    fn iter_raw(&self, low: Bound<Vec<u8>>, high: Bound<Vec<u8>>, order: Order) -> Self::Iter<'_> {
+        let iter = self
+            .inner
+            .iter(low.map(KeyType::<()>::Raw), high.map(KeyType::Raw), order);
+        BlockingStream::new(iter)
+    }
+
+    fn prefix_keys(&self, prefix: &[u8], order: Order) -> Self::Keys<'_> {
+        BlockingStream::new(self.inner.prefix_keys(prefix, order).map_err(Into::into))
+    }
+
+    fn prefix_iter(&self, prefix: &[u8], order: Order) -> Self::Iter<'_> {
+        BlockingStream::new(self.inner.prefix_iter(prefix, order).map_err(Into::into))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::BTreeMap,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use crate::{
+        mock::{DisplayEncoding, FailingStorage},
+        Item, Map, PriorityQueue, StorageError,
+    };
+
+    use super::*;
+
+    type Memory = BlockingStorage<BTreeMap<Vec<u8>, Vec<u8>>>;
+
+    /// Polls `future` to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn collect<S: Stream>(stream: S) -> Vec<S::Item> {
+        block_on(async {
+            let mut stream = pin!(stream);
+            let mut items = Vec::new();
+            while let Some(item) = next(stream.as_mut()).await {
+                items.push(item);
+            }
+            items
+        })
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn test_blocking_storage() {
+        let mut storage = Memory::default();
+        block_on(async {
+            for key in [b"a", b"b", b"c", b"d"] {
+                storage.set_raw(key.to_vec(), key.to_vec()).await.unwrap();
+            }
+            assert_eq!(
+                storage.get_raw(b"b").await,
+                Ok(Some(Cow::Borrowed(&b"b"[..])))
+            );
+            storage.delete_raw(b"b").await.unwrap();
+            assert_eq!(storage.get_raw(b"b").await, Ok(None));
+
+            let mut batch = WriteBatch::new();
+            batch.put(b"e".to_vec(), b"e".to_vec());
+            batch.delete_range(Bound::Included(b"c".to_vec()), Bound::Unbounded);
+            batch.put(b"f".to_vec(), b"f".to_vec());
+            storage.write_batch(batch).await.unwrap();
+        });
+
+        let keys = storage.keys_raw(Bound::Unbounded, Bound::Unbounded, Order::Descending);
+        let keys: Vec<_> = collect(keys).into_iter().map(Result::unwrap).collect();
+        assert_eq!(keys, [&b"f"[..], b"a"]);
+
+        let entries = storage.iter_raw(
+            Bound::Excluded(b"a".to_vec()),
+            Bound::Unbounded,
+            Order::Ascending,
+        );
+        let entries: Vec<_> = collect(entries).into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            entries,
+            [(Cow::Borrowed(&b"f"[..]), Cow::Borrowed(&b"f"[..]))]
+        );
+        assert_eq!(
+            collect(storage.prefix_iter(b"a", Order::Ascending)).len(),
+            1
+        );
+        assert_eq!(storage.into_inner().len(), 2);
+    }
+
+    #[test]
+    fn test_structures() {
+        const ITEM: Item<String, DisplayEncoding> = Item::new(b"item");
+        const MAP: Map<u8, Item<String, DisplayEncoding>> = Map::new(b"map");
+        const QUEUE: PriorityQueue<u8, String, DisplayEncoding> = PriorityQueue::new(b"queue");
+
+        let mut storage = Memory::default();
+        block_on(async {
+            assert_eq!(ITEM.may_load_async(&storage).await, Ok(None));
+            ITEM.save_async(&mut storage, &"foo".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                ITEM.may_load_async(&storage).await,
+                Ok(Some("foo".to_string()))
+            );
+            ITEM.delete_async(&mut storage).await.unwrap();
+            assert_eq!(ITEM.may_load_async(&storage).await, Ok(None));
+
+            for key in 0..5 {
+                let item = MAP.at(key).unwrap();
+                item.save_async(&mut storage, &key.to_string())
+                    .await
+                    .unwrap();
+            }
+            for priority in [3, 1, 2] {
+                QUEUE
+                    .push_async(&mut storage, priority, &priority.to_string())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        // Async and sync scans see the same layout
+        let sync = MAP
+            .range(
+                storage.get_ref(),
+                Bound::Excluded(1),
+                Bound::Included(3),
+                Order::Descending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().1);
+        let stream = MAP
+            .range_async(
+                &storage,
+                Bound::Excluded(1),
+                Bound::Included(3),
+                Order::Descending,
+            )
+            .unwrap();
+        let values: Vec<_> = collect(stream)
+            .into_iter()
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(values, ["3", "2"]);
+        assert!(values.into_iter().eq(sync));
+
+        let all = MAP
+            .range_async(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap();
+        assert_eq!(collect(all).len(), 5);
+
+        block_on(async {
+            let peeked = QUEUE.peek_async(&storage, Order::Descending).await;
+            assert_eq!(peeked, Ok(Some((3, "3".to_string()))));
+            assert_eq!(QUEUE.peek(storage.get_ref(), Order::Descending), peeked);
+
+            let mut popped = Vec::new();
+            while let Some((priority, _)) = QUEUE
+                .pop_async(&mut storage, Order::Ascending)
+                .await
+                .unwrap()
+            {
+                popped.push(priority);
+            }
+            assert_eq!(popped, [1, 2, 3]);
+        });
+    }
+
+    #[test]
+    fn test_backend_error() {
+        const ITEM: Item<String, DisplayEncoding> = Item::new(b"foo");
+        let mut storage = BlockingStorage::new(FailingStorage::default());
+        block_on(async {
+            ITEM.save_async(&mut storage, &"bar".to_string())
+                .await
+                .unwrap();
+            storage.get_mut().failing = true;
+            let error = || StorageError::Backend(BackendError::new("disk on fire"));
+            assert_eq!(ITEM.may_load_async(&storage).await, Err(error()));
+            assert_eq!(ITEM.delete_async(&mut storage).await, Err(error()));
+        });
+        storage.get_mut().failing = false;
+        assert_eq!(
+            ITEM.may_load(storage.get_ref()),
+            Ok(Some("bar".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_futures_are_send() {
+        const QUEUE: PriorityQueue<u8, String, DisplayEncoding> = PriorityQueue::new(b"queue");
+        let mut storage = Memory::default();
+        assert_send(&storage.get(&b"key".to_vec()));
+        assert_send(&storage.write_batch(WriteBatch::new()));
+        assert_send(&storage.iter_raw(Bound::Unbounded, Bound::Unbounded, Order::Ascending));
+        assert_send(&QUEUE.peek_async(&storage, Order::Ascending));
+        assert_send(&QUEUE.pop_async(&mut storage, Order::Ascending));
+    }
+}
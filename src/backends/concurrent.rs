@@ -0,0 +1,471 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    marker::PhantomData,
+    ops::Bound,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{
+    storage::{encode_bound, is_empty_range, key_of, KeyRange},
+    BackendError, BatchOp, Encodable, IterableStorage, KeyEncoding, KeyResult, KvResult, Order,
+    RawStorageError, SharedStorageMut, Storage, StorageMut, WriteBatch,
+};
+
+/// The number of shards of [`ConcurrentStorage::new`].
+const DEFAULT_SHARDS: usize = 16;
+
+type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+/// A shard's entries, shared with the iterators reading a snapshot of them.
+type Shard = Arc<Entries>;
+
+/// Applies `op` to the entries of one shard, holding only the keys that hash to
+/// it. Entries are only cloned if an iterator still holds a snapshot of them.
+fn apply(shard: &mut Shard, op: &BatchOp) {
+    match op {
+        BatchOp::Put(key, value) => {
+            Arc::make_mut(shard).insert(key.clone(), value.clone());
+        }
+        BatchOp::Delete(key) => {
+            if shard.contains_key(key) {
+                Arc::make_mut(shard).remove(key);
+            }
+        }
+        BatchOp::DeleteRange(low, high) => {
+            if is_empty_range(low, high)
+                || shard.range((low.clone(), high.clone())).next().is_none()
+            {
+                return;
+            }
+            let entries = Arc::make_mut(shard);
+            // BTreeMap deletes are infallible
+            let _ = entries.delete_range_raw(low.clone(), high.clone());
+        }
+    }
+}
+
+/// A thread-safe in-memory storage backend, whose writes take `&self`.
+///
+/// Keys are spread by hash over shards, each a `BTreeMap` behind its own
+/// `RwLock`, so that writes to different shards do not contend. Writes to
+/// several shards, such as a [`WriteBatch`] or a range delete, lock all of
+/// them at once, and so are atomic.
+///
+/// Iterators read from a snapshot of every shard, taken when they are created
+/// with all shards locked for reading, so a range scan never sees part of a
+/// batch. Snapshots are shared, not copied: a shard is only copied by the
+/// first write to it while an iterator still holds its snapshot. Iterators
+/// merge the shards, seeking each shard once per entry it yields.
+///
+/// [`ConcurrentStorage::update`] runs a read-modify-write, such as a
+/// [`PriorityQueue::pop`](crate::PriorityQueue::pop), with every shard locked.
+pub struct ConcurrentStorage {
+    shards: Box<[RwLock<Shard>]>,
+}
+
+impl Default for ConcurrentStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConcurrentStorage {
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Creates a storage with `shards` shards. More shards let more writers
+    /// proceed in parallel, and make every range scan merge more shards.
+    ///
+    /// # Panics
+    ///
+    /// If `shards` is zero.
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "ConcurrentStorage needs at least one shard");
+        Self {
+            shards: (0..shards).map(|_| RwLock::default()).collect(),
+        }
+    }
+
+    fn shard_of(&self, key: &[u8]) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+
+    // Every write leaves its shard consistent, so poison is ignored. Only the
+    // closure of an `update` can panic while holding locks, and its writes up
+    // to the panic stay visible, as when it fails
+    fn read(&self, shard: usize) -> RwLockReadGuard<'_, Shard> {
+        self.shards[shard]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, shard: usize) -> RwLockWriteGuard<'_, Shard> {
+        self.shards[shard]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks every shard for writing, in order, so that lockers of several
+    /// shards never deadlock.
+    fn write_all(&self) -> Vec<RwLockWriteGuard<'_, Shard>> {
+        (0..self.shards.len()).map(|i| self.write(i)).collect()
+    }
+
+    /// Takes a consistent snapshot of every shard.
+    fn snapshot(&self) -> Vec<Shard> {
+        let guards: Vec<_> = (0..self.shards.len()).map(|i| self.read(i)).collect();
+        guards.iter().map(|shard| Arc::clone(shard)).collect()
+    }
+
+    /// Runs `f` with every shard locked for writing, so that its reads and
+    /// writes are isolated from other threads, which see its writes all at
+    /// once. Writes are not rolled back if `f` fails or panics; wrap the
+    /// storage in a [`Transaction`](crate::Transaction) for that.
+    pub fn update<R>(&self, f: impl FnOnce(&mut ConcurrentUpdate<'_>) -> R) -> R {
+        let mut update = ConcurrentUpdate {
+            storage: self,
+            shards: self.write_all(),
+        };
+        f(&mut update)
+    }
+}
+
+impl Storage for ConcurrentStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        let shard = self.read(self.shard_of(key));
+        Ok(shard.get(key).map(|value| Cow::Owned(value.clone())))
+    }
+}
+
+impl SharedStorageMut for ConcurrentStorage {
+    fn set_raw(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        let mut shard = self.write(self.shard_of(&key));
+        Arc::make_mut(&mut shard).insert(key, value);
+        Ok(())
+    }
+
+    fn delete_raw(&self, key: &[u8]) -> Result<(), BackendError> {
+        let mut shard = self.write(self.shard_of(key));
+        apply(&mut shard, &BatchOp::Delete(key.to_vec()));
+        Ok(())
+    }
+
+    fn delete_range_raw(
+        &self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        let op = BatchOp::DeleteRange(low, high);
+        for mut shard in self.write_all() {
+            apply(&mut shard, &op);
+        }
+        Ok(())
+    }
+
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), BackendError> {
+        let mut shards = BTreeSet::new();
+        for op in &batch {
+            match op {
+                BatchOp::Put(key, _) | BatchOp::Delete(key) => {
+                    shards.insert(self.shard_of(key));
+                }
+                BatchOp::DeleteRange(..) => shards.extend(0..self.shards.len()),
+            }
+        }
+        let mut guards: BTreeMap<_, _> = shards.into_iter().map(|i| (i, self.write(i))).collect();
+        for op in &batch {
+            match op {
+                BatchOp::Put(key, _) | BatchOp::Delete(key) => {
+                    apply(guards.get_mut(&self.shard_of(key)).unwrap(), op)
+                }
+                BatchOp::DeleteRange(..) => {
+                    guards.values_mut().for_each(|shard| apply(shard, op));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Exclusive access to a [`ConcurrentStorage`], within
+/// [`ConcurrentStorage::update`].
+pub struct ConcurrentUpdate<'a> {
+    storage: &'a ConcurrentStorage,
+    shards: Vec<RwLockWriteGuard<'a, Shard>>,
+}
+
+impl ConcurrentUpdate<'_> {
+    fn snapshot(&self) -> Vec<Shard> {
+        self.shards.iter().map(|shard| Arc::clone(shard)).collect()
+    }
+
+    fn shard(&mut self, key: &[u8]) -> &mut Shard {
+        let i = self.storage.shard_of(key);
+        &mut self.shards[i]
+    }
+}
+
+impl Storage for ConcurrentUpdate<'_> {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        let shard = &self.shards[self.storage.shard_of(key)];
+        Ok(shard.get(key).map(|value| Cow::Borrowed(value.as_slice())))
+    }
+}
+
+impl StorageMut for ConcurrentUpdate<'_> {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        Arc::make_mut(self.shard(&key)).insert(key, value);
+        Ok(())
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        apply(self.shard(key), &BatchOp::Delete(key.to_vec()));
+        Ok(())
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        let op = BatchOp::DeleteRange(low, high);
+        self.shards.iter_mut().for_each(|shard| apply(shard, &op));
+        Ok(())
+    }
+}
+
+/// Iterator over a range of a [`ConcurrentStorage`], merging a snapshot of its
+/// shards in either [`Order`].
+pub struct ConcurrentIter<'a> {
+    shards: Vec<Shard>,
+    /// The range left to read in each shard, narrowed past each entry read.
+    ranges: Vec<KeyRange>,
+    /// The next entry of each shard.
+    heads: Vec<Option<(Vec<u8>, Vec<u8>)>>,
+    order: Order,
+    _storage: PhantomData<&'a ConcurrentStorage>,
+}
+
+impl ConcurrentIter<'_> {
+    fn new(shards: Vec<Shard>, low: Bound<Vec<u8>>, high: Bound<Vec<u8>>, order: Order) -> Self {
+        let shards = match is_empty_range(&low, &high) {
+            true => Vec::new(),
+            false => shards,
+        };
+        let mut iter = Self {
+            ranges: vec![(low, high); shards.len()],
+            heads: vec![None; shards.len()],
+            shards,
+            order,
+            _storage: PhantomData,
+        };
+        for i in 0..iter.shards.len() {
+            iter.advance(i);
+        }
+        iter
+    }
+
+    /// Reads the next entry of shard `i` into its head.
+    fn advance(&mut self, i: usize) {
+        let (low, high) = &self.ranges[i];
+        if is_empty_range(low, high) {
+            self.heads[i] = None;
+            return;
+        }
+        let mut range = self.shards[i].range((low.clone(), high.clone()));
+        let entry = match self.order {
+            Order::Ascending => range.next(),
+            Order::Descending => range.next_back(),
+        };
+        self.heads[i] = entry.map(|(key, value)| (key.clone(), value.clone()));
+        if let Some((key, _)) = &self.heads[i] {
+            match self.order {
+                Order::Ascending => self.ranges[i].0 = Bound::Excluded(key.clone()),
+                Order::Descending => self.ranges[i].1 = Bound::Excluded(key.clone()),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for ConcurrentIter<'a> {
+    type Item = KvResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let heads = self.heads.iter().enumerate();
+        let heads = heads.filter_map(|(i, head)| Some((i, &head.as_ref()?.0)));
+        let (i, _) = match self.order {
+            Order::Ascending => heads.min_by_key(|(_, key)| *key),
+            Order::Descending => heads.max_by_key(|(_, key)| *key),
+        }?;
+        let (key, value) = self.heads[i].take()?;
+        self.advance(i);
+        Some(Ok((Cow::Owned(key), Cow::Owned(value))))
+    }
+}
+
+/// Implements [`IterableStorage`] for a type with a `snapshot` of its shards.
+macro_rules! impl_concurrent_iterable {
+    ($storage:ty) => {
+        impl IterableStorage for $storage {
+            type Keys<'a>
+                = std::iter::Map<ConcurrentIter<'a>, fn(KvResult<'a>) -> KeyResult<'a>>
+            where
+                Self: 'a;
+            type Iter<'a>
+                = ConcurrentIter<'a>
+            where
+                Self: 'a;
+
+            fn keys<K: Encodable<KeyEncoding>>(
+                &self,
+                low: Bound<K>,
+                high: Bound<K>,
+                order: Order,
+            ) -> Result<Self::Keys<'_>, RawStorageError> {
+                let iter = self.iter(low, high, order)?;
+                Ok(iter.map(key_of as fn(_) -> _))
+            }
+
+            fn iter<K: Encodable<KeyEncoding>>(
+                &self,
+                low: Bound<K>,
+                high: Bound<K>,
+                order: Order,
+            ) -> Result<Self::Iter<'_>, RawStorageError> {
+                let (low, high) = (encode_bound!(low), encode_bound!(high));
+                Ok(ConcurrentIter::new(self.snapshot(), low, high, order))
+            }
+        }
+    };
+}
+
+impl_concurrent_iterable!(ConcurrentStorage);
+impl_concurrent_iterable!(ConcurrentUpdate<'_>);
+
+#[cfg(test)]
+mod test {
+    use std::{panic, sync::Mutex};
+
+    use crate::{mock::DisplayEncoding, testing::Fixture, Item, KeyType, Map, PriorityQueue};
+
+    use super::*;
+
+    impl Fixture for ConcurrentStorage {
+        type Storage<'a> = &'a ConcurrentStorage;
+
+        fn storage(&mut self) -> Self::Storage<'_> {
+            self
+        }
+    }
+
+    crate::storage_conformance_tests!(ConcurrentStorage::new);
+
+    mod one_shard {
+        use super::*;
+
+        crate::storage_conformance_tests!(|| ConcurrentStorage::with_shards(1));
+    }
+
+    #[test]
+    fn test_panicking_update() {
+        let storage = ConcurrentStorage::new();
+        storage.set_raw(b"a".to_vec(), b"old".to_vec()).unwrap();
+        let result = panic::catch_unwind(|| {
+            storage.update(|update| {
+                update.set_raw(b"a".to_vec(), b"new".to_vec()).unwrap();
+                update
+                    .delete_range_raw(Bound::Unbounded, Bound::Unbounded)
+                    .unwrap();
+                update.set_raw(b"b".to_vec(), b"new".to_vec()).unwrap();
+                panic!("update failed");
+            })
+        });
+        assert!(result.is_err());
+
+        // The writes before the panic stay, and the storage remains usable
+        assert_eq!(storage.get_raw(b"a"), Ok(None));
+        assert_eq!(storage.get_raw(b"b"), Ok(Some(Cow::Owned(b"new".to_vec()))));
+        storage.set_raw(b"c".to_vec(), b"new".to_vec()).unwrap();
+        let keys: Vec<_> = storage
+            .prefix_keys(b"", Order::Ascending)
+            .unwrap()
+            .map(|key| key.unwrap().into_owned())
+            .collect();
+        assert_eq!(keys, [b"b".to_vec(), b"c".to_vec()]);
+    }
+
+    #[test]
+    fn test_threads() {
+        const THREADS: u32 = 8;
+        const PER_THREAD: u32 = 100;
+        let pq: PriorityQueue<u32, u32, DisplayEncoding> = PriorityQueue::new(b"pq");
+        let counts: Map<u32, Item<u32, DisplayEncoding>> = Map::new(b"counts");
+        let storage = ConcurrentStorage::new();
+
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let (storage, pq, counts) = (&storage, &pq, &counts);
+                scope.spawn(move || {
+                    let mut storage = storage;
+                    for i in 0..PER_THREAD {
+                        pq.push(&mut storage, thread * PER_THREAD + i, &thread)
+                            .unwrap();
+                        let count = counts.at(thread).unwrap();
+                        count.save(&mut storage, &(i + 1)).unwrap();
+                    }
+                });
+            }
+        });
+        for thread in 0..THREADS {
+            let count = counts.at(thread).unwrap().may_load(&storage);
+            assert_eq!(count, Ok(Some(PER_THREAD)));
+        }
+
+        // Pops are read-modify-writes, so each item is popped exactly once
+        let popped = Mutex::new(Vec::new());
+        std::thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| loop {
+                    let item = storage.update(|storage| pq.pop(storage, Order::Ascending));
+                    match item.unwrap() {
+                        Some((priority, _)) => popped.lock().unwrap().push(priority),
+                        None => break,
+                    }
+                });
+            }
+        });
+        let mut popped = popped.into_inner().unwrap();
+        popped.sort();
+        assert_eq!(popped, (0..THREADS * PER_THREAD).collect::<Vec<_>>());
+        assert_eq!(pq.peek(&storage, Order::Ascending), Ok(None));
+    }
+
+    #[test]
+    fn test_iter_is_snapshot() {
+        let storage = ConcurrentStorage::new();
+        let mut batch = WriteBatch::new();
+        for i in 0..100u8 {
+            batch.put(vec![i], vec![i]);
+        }
+        storage.write_batch(batch).unwrap();
+
+        let all = || (Bound::<KeyType<()>>::Unbounded, Bound::Unbounded);
+        let (low, high) = all();
+        let mut iter = storage.iter(low, high, Order::Descending).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().0.as_ref(), [99]);
+        storage.set_raw(vec![200], vec![]).unwrap();
+        storage
+            .delete_range_raw(Bound::Unbounded, Bound::Excluded(vec![50]))
+            .unwrap();
+        assert_eq!(iter.count(), 99);
+
+        let (low, high) = all();
+        let keys = storage.keys(low, high, Order::Ascending).unwrap();
+        let keys: Vec<_> = keys.map(|key| key.unwrap()[0]).collect();
+        assert_eq!(keys, (50..100).chain([200]).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,272 @@
+use std::{borrow::Cow, ops::Bound, path::Path};
+
+use ::fjall::{Database, Guard, Keyspace, KeyspaceCreateOptions};
+
+use super::resolve_batch;
+use crate::{
+    storage::{encode_bound, is_empty_range},
+    BackendError, Directed, Encodable, IterableStorage, KeyEncoding, KeyResult, KvResult, Order,
+    RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+/// The keyspace used by [`FjallStorage::open`].
+const DEFAULT_KEYSPACE: &str = "libkv";
+
+/// Byte prepended to every stored key, since fjall does not accept empty keys.
+const KEY_TAG: u8 = 0;
+/// The longest key fjall accepts, including [`KEY_TAG`].
+const MAX_KEY_LEN: usize = u16::MAX as usize;
+
+fn stored_key(key: &[u8]) -> Result<Vec<u8>, BackendError> {
+    if key.len() >= MAX_KEY_LEN {
+        return Err(BackendError::new(format!(
+            "key of {} bytes exceeds fjall's limit of {} bytes",
+            key.len(),
+            MAX_KEY_LEN - 1
+        )));
+    }
+    Ok([&[KEY_TAG], key].concat())
+}
+
+/// Converts a bound on keys into a bound on stored keys. Keys too long to be
+/// stored are still valid bounds, since fjall only limits the keys it stores.
+fn stored_bound(bound: &Bound<Vec<u8>>) -> Bound<Vec<u8>> {
+    bound
+        .as_ref()
+        .map(|key| [&[KEY_TAG], key.as_slice()].concat())
+}
+
+/// A storage backend over a single keyspace of a fjall database.
+///
+/// Batches are committed atomically with fjall's own write batches. fjall has
+/// no native range delete, so range deletes scan the keys in range first, and
+/// are only atomic with respect to the rest of their batch. Iterators read from
+/// a snapshot taken when they are created.
+///
+/// fjall rejects empty keys, so every key is stored behind a one-byte tag, and
+/// keys are limited to 65534 bytes.
+pub struct FjallStorage {
+    db: Database,
+    keyspace: Keyspace,
+}
+
+impl FjallStorage {
+    /// Opens or creates the database at `path`, using its `libkv` keyspace.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        let db = Database::builder(path.as_ref())
+            .open()
+            .map_err(BackendError::new)?;
+        let keyspace = db
+            .keyspace(DEFAULT_KEYSPACE, KeyspaceCreateOptions::default)
+            .map_err(BackendError::new)?;
+        Ok(Self::new(db, keyspace))
+    }
+
+    /// Uses `keyspace`, which must belong to `db`.
+    pub const fn new(db: Database, keyspace: Keyspace) -> Self {
+        Self { db, keyspace }
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    pub fn keyspace(&self) -> &Keyspace {
+        &self.keyspace
+    }
+
+    pub fn into_inner(self) -> (Database, Keyspace) {
+        (self.db, self.keyspace)
+    }
+
+    fn range(&self, low: Bound<Vec<u8>>, high: Bound<Vec<u8>>) -> Option<::fjall::Iter> {
+        match is_empty_range(&low, &high) {
+            true => None,
+            false => Some(
+                self.keyspace
+                    .range((stored_bound(&low), stored_bound(&high))),
+            ),
+        }
+    }
+}
+
+impl Storage for FjallStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        if key.len() >= MAX_KEY_LEN {
+            return Ok(None);
+        }
+        let value = self
+            .keyspace
+            .get(stored_key(key)?)
+            .map_err(BackendError::new)?;
+        Ok(value.map(|value| Cow::Owned(value.to_vec())))
+    }
+}
+
+impl StorageMut for FjallStorage {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.keyspace
+            .insert(stored_key(&key)?, value)
+            .map_err(BackendError::new)
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        if key.len() >= MAX_KEY_LEN {
+            return Ok(());
+        }
+        self.keyspace
+            .remove(stored_key(key)?)
+            .map_err(BackendError::new)
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        let mut batch = WriteBatch::new();
+        batch.delete_range(low, high);
+        self.write_batch(batch)
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        let writes = resolve_batch(batch, |low, high| {
+            self.keyspace
+                .range((stored_bound(low), stored_bound(high)))
+                .map(|guard| {
+                    guard
+                        .key()
+                        .map(|key| key[1..].to_vec())
+                        .map_err(BackendError::new)
+                })
+                .collect()
+        })?;
+
+        let mut fjall_batch = self.db.batch();
+        for (key, value) in writes {
+            match value {
+                Some(value) => fjall_batch.insert(&self.keyspace, stored_key(&key)?, value),
+                None if key.len() >= MAX_KEY_LEN => {}
+                None => fjall_batch.remove(&self.keyspace, stored_key(&key)?),
+            }
+        }
+        fjall_batch.commit().map_err(BackendError::new)
+    }
+}
+
+fn owned_kv<'a>(guard: Guard) -> KvResult<'a> {
+    let (key, value) = guard.into_inner().map_err(BackendError::new)?;
+    Ok((Cow::Owned(key[1..].to_vec()), Cow::Owned(value.to_vec())))
+}
+
+fn owned_k<'a>(guard: Guard) -> KeyResult<'a> {
+    let key = guard.key().map_err(BackendError::new)?;
+    Ok(Cow::Owned(key[1..].to_vec()))
+}
+
+impl IterableStorage for FjallStorage {
+    type Keys<'a> = Directed<std::iter::Map<::fjall::Iter, fn(Guard) -> KeyResult<'a>>>;
+    type Iter<'a> = Directed<std::iter::Map<::fjall::Iter, fn(Guard) -> KvResult<'a>>>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        match self.range(encode_bound!(low), encode_bound!(high)) {
+            Some(iter) => Ok(Directed::new(iter.map(owned_k as fn(_) -> _), order)),
+            None => Ok(Directed::empty(order)),
+        }
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        match self.range(encode_bound!(low), encode_bound!(high)) {
+            Some(iter) => Ok(Directed::new(iter.map(owned_kv as fn(_) -> _), order)),
+            None => Ok(Directed::empty(order)),
+        }
+    }
+
+    fn prefix_keys(&self, prefix: &[u8], order: Order) -> Result<Self::Keys<'_>, BackendError> {
+        let prefix = [&[KEY_TAG], prefix].concat();
+        let iter = self.keyspace.prefix(prefix).map(owned_k as fn(_) -> _);
+        Ok(Directed::new(iter, order))
+    }
+
+    fn prefix_iter(&self, prefix: &[u8], order: Order) -> Result<Self::Iter<'_>, BackendError> {
+        let prefix = [&[KEY_TAG], prefix].concat();
+        let iter = self.keyspace.prefix(prefix).map(owned_kv as fn(_) -> _);
+        Ok(Directed::new(iter, order))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::fjall::PersistMode;
+    use tempfile::TempDir;
+
+    use crate::{
+        mock::DisplayEncoding,
+        testing::{Borrowed, Fixture},
+        Item, Vector,
+    };
+
+    use super::*;
+
+    /// A fresh database, whose directory is removed once it is dropped.
+    struct Temporary {
+        storage: FjallStorage,
+        _dir: TempDir,
+    }
+
+    impl Temporary {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let db = Database::builder(dir.path())
+                .temporary(true)
+                .open()
+                .unwrap();
+            let keyspace = db.keyspace("test", KeyspaceCreateOptions::default).unwrap();
+            let storage = FjallStorage::new(db, keyspace);
+            Self { storage, _dir: dir }
+        }
+    }
+
+    impl Fixture for Temporary {
+        type Storage<'a> = Borrowed<'a, FjallStorage>;
+
+        fn storage(&mut self) -> Self::Storage<'_> {
+            Borrowed(&mut self.storage)
+        }
+    }
+
+    crate::storage_conformance_tests!(Temporary::new);
+
+    #[test]
+    fn test_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        const VECTOR: Vector<Item<u32, DisplayEncoding>> = Vector::new(b"vec");
+        {
+            let mut storage = FjallStorage::open(dir.path()).unwrap();
+            for i in 0..3 {
+                VECTOR.push(&mut storage, &i).unwrap();
+            }
+            VECTOR.truncate(&mut storage, 2).unwrap();
+            storage.database().persist(PersistMode::SyncAll).unwrap();
+        }
+
+        let storage = FjallStorage::open(dir.path()).unwrap();
+        assert_eq!(VECTOR.len(&storage), Ok(2));
+        let values: Vec<_> = VECTOR
+            .iter(&storage, Order::Descending)
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(values, vec![1, 0]);
+    }
+}
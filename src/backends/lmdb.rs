@@ -0,0 +1,518 @@
+use std::{borrow::Cow, ops::Bound};
+
+use ::heed::{types::Bytes, Database, Env, RoRange, RoRevRange, RoTxn, RwTxn, WithoutTls};
+
+use crate::{
+    storage::{encode_bound, is_empty_range, key_of, Page, PagedScan},
+    BackendError, BatchOp, Encodable, IterableStorage, KeyEncoding, KeyResult, KvResult, Order,
+    RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+/// The database used by [`LmdbStorage::new`].
+const DEFAULT_DATABASE: &str = "libkv";
+
+/// Byte prepended to every stored key, since LMDB does not accept empty keys.
+const KEY_TAG: u8 = 0;
+
+/// Entries read per read transaction by the iterators of an [`LmdbStorage`].
+const PAGE_SIZE: usize = 256;
+
+fn stored_key(key: &[u8]) -> Vec<u8> {
+    [&[KEY_TAG], key].concat()
+}
+
+fn stored_bound(bound: &Bound<Vec<u8>>) -> Bound<Vec<u8>> {
+    bound.as_ref().map(|key| stored_key(key))
+}
+
+fn as_slices(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    bound.as_ref().map(Vec::as_slice)
+}
+
+type Db = Database<Bytes, Bytes>;
+
+/// A storage backend over a single database of an LMDB environment, through
+/// `heed`.
+///
+/// `LmdbStorage` itself reads in a fresh read transaction, and commits a write
+/// transaction per write or [`WriteBatch`]. Its iterators copy a page of entries
+/// out of each read transaction, so they may see writes committed between pages.
+/// [`LmdbStorage::read_txn`] and [`LmdbStorage::write_txn`] return transactions
+/// implementing the storage traits, whose reads and iterators see a single
+/// snapshot and borrow straight from the memory map.
+///
+/// The environment must be opened with `EnvOpenOptions::read_txn_without_tls`,
+/// so that read transactions are not tied to their thread: `LmdbStorage` can then
+/// read while the same thread holds an [`LmdbReadTxn`], and read transactions
+/// can be sent to other threads.
+///
+/// LMDB rejects empty keys, so every key is stored behind a one-byte tag. Keys
+/// are limited to the environment's maximum key size minus one byte, 510 bytes
+/// by default.
+#[derive(Debug, Clone)]
+pub struct LmdbStorage {
+    env: Env<WithoutTls>,
+    db: Db,
+}
+
+impl LmdbStorage {
+    /// Uses the `libkv` database of `env`, creating it if missing. `env` must
+    /// be opened with room for at least one named database.
+    pub fn new(env: Env<WithoutTls>) -> Result<Self, BackendError> {
+        let mut txn = env.write_txn().map_err(BackendError::new)?;
+        let db = env
+            .create_database(&mut txn, Some(DEFAULT_DATABASE))
+            .map_err(BackendError::new)?;
+        txn.commit().map_err(BackendError::new)?;
+        Ok(Self::with_database(env, db))
+    }
+
+    /// Uses `db`, which must belong to `env`.
+    pub const fn with_database(env: Env<WithoutTls>, db: Db) -> Self {
+        Self { env, db }
+    }
+
+    pub fn env(&self) -> &Env<WithoutTls> {
+        &self.env
+    }
+
+    pub fn database(&self) -> Db {
+        self.db
+    }
+
+    /// Begins a read transaction, which sees a snapshot of the database.
+    pub fn read_txn(&self) -> Result<LmdbReadTxn<'_>, BackendError> {
+        let txn = self.env.read_txn().map_err(BackendError::new)?;
+        Ok(LmdbReadTxn { txn, db: self.db })
+    }
+
+    /// Begins a write transaction. Its writes are discarded unless it is
+    /// committed with [`LmdbWriteTxn::commit`].
+    pub fn write_txn(&self) -> Result<LmdbWriteTxn<'_>, BackendError> {
+        let txn = self.env.write_txn().map_err(BackendError::new)?;
+        Ok(LmdbWriteTxn { txn, db: self.db })
+    }
+
+    fn write(&self, op: BatchOp) -> Result<(), BackendError> {
+        let mut txn = self.write_txn()?;
+        txn.apply(op)?;
+        txn.commit()
+    }
+}
+
+impl Storage for LmdbStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        let txn = self.read_txn()?;
+        let value = txn.get_raw(key)?;
+        Ok(value.map(|value| Cow::Owned(value.into_owned())))
+    }
+}
+
+impl StorageMut for LmdbStorage {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.write(BatchOp::Put(key, value))
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.write(BatchOp::Delete(key.to_vec()))
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        self.write(BatchOp::DeleteRange(low, high))
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        let mut txn = self.write_txn()?;
+        txn.write_batch(batch)?;
+        txn.commit()
+    }
+}
+
+/// Iterator over a range of an [`LmdbStorage`], reading a page of entries per
+/// read transaction.
+pub struct LmdbStorageIter<'a> {
+    storage: &'a LmdbStorage,
+    keys_only: bool,
+    scan: PagedScan,
+}
+
+impl LmdbStorage {
+    /// Reads the first [`PAGE_SIZE`] entries between `low` and `high`, in
+    /// `order`, in a fresh read transaction.
+    fn fetch(
+        &self,
+        low: &Bound<Vec<u8>>,
+        high: &Bound<Vec<u8>>,
+        order: Order,
+        keys_only: bool,
+    ) -> Result<(Page, bool), BackendError> {
+        let txn = self.read_txn()?;
+        let mut page = Vec::new();
+        for entry in range(txn.db, &txn.txn, low.clone(), high.clone(), order)?.take(PAGE_SIZE) {
+            let (key, value) = entry?;
+            let value = match keys_only {
+                true => Vec::new(),
+                false => value.into_owned(),
+            };
+            page.push((key.into_owned(), value));
+        }
+        let last = page.len() < PAGE_SIZE;
+        Ok((page, last))
+    }
+
+    fn scan(
+        &self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+        order: Order,
+        keys_only: bool,
+    ) -> LmdbStorageIter<'_> {
+        LmdbStorageIter {
+            storage: self,
+            keys_only,
+            scan: PagedScan::new(low, high, order),
+        }
+    }
+}
+
+impl<'a> Iterator for LmdbStorageIter<'a> {
+    type Item = KvResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (storage, keys_only) = (self.storage, self.keys_only);
+        self.scan
+            .next(|low, high, order| storage.fetch(low, high, order, keys_only))
+    }
+}
+
+impl IterableStorage for LmdbStorage {
+    type Keys<'a> = std::iter::Map<LmdbStorageIter<'a>, fn(KvResult<'a>) -> KeyResult<'a>>;
+    type Iter<'a> = LmdbStorageIter<'a>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        let iter = self.scan(encode_bound!(low), encode_bound!(high), order, true);
+        Ok(iter.map(key_of as fn(_) -> _))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        Ok(self.scan(encode_bound!(low), encode_bound!(high), order, false))
+    }
+}
+
+/// A read transaction of an [`LmdbStorage`].
+pub struct LmdbReadTxn<'env> {
+    txn: RoTxn<'env, WithoutTls>,
+    db: Db,
+}
+
+/// A write transaction of an [`LmdbStorage`]. Reads and iterators see the
+/// transaction's own writes.
+pub struct LmdbWriteTxn<'env> {
+    txn: RwTxn<'env>,
+    db: Db,
+}
+
+impl LmdbWriteTxn<'_> {
+    pub fn commit(self) -> Result<(), BackendError> {
+        self.txn.commit().map_err(BackendError::new)
+    }
+
+    /// Discards the transaction's writes. Dropping the transaction does the same.
+    pub fn abort(self) {
+        self.txn.abort()
+    }
+
+    fn apply(&mut self, op: BatchOp) -> Result<(), BackendError> {
+        let result = match op {
+            BatchOp::Put(key, value) => self.db.put(&mut self.txn, &stored_key(&key), &value),
+            BatchOp::Delete(key) => self.db.delete(&mut self.txn, &stored_key(&key)).map(drop),
+            BatchOp::DeleteRange(low, high) => {
+                if is_empty_range(&low, &high) {
+                    return Ok(());
+                }
+                let (low, high) = (stored_bound(&low), stored_bound(&high));
+                let range = (as_slices(&low), as_slices(&high));
+                self.db.delete_range(&mut self.txn, &range).map(drop)
+            }
+        };
+        result.map_err(BackendError::new)
+    }
+}
+
+/// Reads `key` within `txn`, borrowing the value from the memory map.
+fn get<'txn>(db: Db, txn: &'txn RoTxn, key: &[u8]) -> Result<Option<&'txn [u8]>, BackendError> {
+    db.get(txn, &stored_key(key)).map_err(BackendError::new)
+}
+
+/// Iterator over a range of an LMDB database, in either [`Order`].
+pub enum LmdbIter<'txn> {
+    Ascending(RoRange<'txn, Bytes, Bytes>),
+    Descending(RoRevRange<'txn, Bytes, Bytes>),
+    Empty,
+}
+
+type LmdbEntry<'txn> = ::heed::Result<(&'txn [u8], &'txn [u8])>;
+
+impl<'txn> Iterator for LmdbIter<'txn> {
+    type Item = KvResult<'txn>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry: LmdbEntry<'txn> = match self {
+            Self::Ascending(iter) => iter.next()?,
+            Self::Descending(iter) => iter.next()?,
+            Self::Empty => return None,
+        };
+        let entry = entry.map(|(key, value)| (Cow::Borrowed(&key[1..]), Cow::Borrowed(value)));
+        Some(entry.map_err(BackendError::new))
+    }
+}
+
+fn range<'txn>(
+    db: Db,
+    txn: &'txn RoTxn,
+    low: Bound<Vec<u8>>,
+    high: Bound<Vec<u8>>,
+    order: Order,
+) -> Result<LmdbIter<'txn>, BackendError> {
+    if is_empty_range(&low, &high) {
+        return Ok(LmdbIter::Empty);
+    }
+    let (low, high) = (stored_bound(&low), stored_bound(&high));
+    let range = (as_slices(&low), as_slices(&high));
+    let iter = match order {
+        Order::Ascending => db.range(txn, &range).map(LmdbIter::Ascending),
+        Order::Descending => db.rev_range(txn, &range).map(LmdbIter::Descending),
+    };
+    iter.map_err(BackendError::new)
+}
+
+/// Implements the storage traits for a transaction type, whose `txn` field
+/// dereferences to a `RoTxn`.
+macro_rules! impl_lmdb_txn {
+    ($txn:ident) => {
+        impl Storage for $txn<'_> {
+            fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+                Ok(get(self.db, &self.txn, key)?.map(Cow::Borrowed))
+            }
+        }
+
+        impl IterableStorage for $txn<'_> {
+            type Keys<'a>
+                = std::iter::Map<LmdbIter<'a>, fn(KvResult<'a>) -> KeyResult<'a>>
+            where
+                Self: 'a;
+            type Iter<'a>
+                = LmdbIter<'a>
+            where
+                Self: 'a;
+
+            fn keys<K: Encodable<KeyEncoding>>(
+                &self,
+                low: Bound<K>,
+                high: Bound<K>,
+                order: Order,
+            ) -> Result<Self::Keys<'_>, RawStorageError> {
+                let iter = self.iter(low, high, order)?;
+                Ok(iter.map(key_of as fn(_) -> _))
+            }
+
+            fn iter<K: Encodable<KeyEncoding>>(
+                &self,
+                low: Bound<K>,
+                high: Bound<K>,
+                order: Order,
+            ) -> Result<Self::Iter<'_>, RawStorageError> {
+                let (low, high) = (encode_bound!(low), encode_bound!(high));
+                Ok(range(self.db, &self.txn, low, high, order)?)
+            }
+        }
+    };
+}
+
+impl_lmdb_txn!(LmdbReadTxn);
+impl_lmdb_txn!(LmdbWriteTxn);
+
+impl StorageMut for LmdbWriteTxn<'_> {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.apply(BatchOp::Put(key, value))
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.apply(BatchOp::Delete(key.to_vec()))
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        self.apply(BatchOp::DeleteRange(low, high))
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        batch.into_iter().try_for_each(|op| self.apply(op))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use ::heed::EnvOpenOptions;
+
+    use tempfile::TempDir;
+
+    use crate::{
+        mock::DisplayEncoding,
+        testing::{Borrowed, Fixture},
+        Order, PriorityQueue,
+    };
+
+    use super::*;
+
+    fn open(path: &Path) -> LmdbStorage {
+        // SAFETY: every test opens its own directory, once at a time
+        let env = unsafe {
+            EnvOpenOptions::new()
+                .read_txn_without_tls()
+                .map_size(16 * 1024 * 1024)
+                .max_dbs(1)
+                .open(path)
+                .unwrap()
+        };
+        LmdbStorage::new(env).unwrap()
+    }
+
+    /// A fresh environment, whose directory is removed once it is closed.
+    struct Temporary {
+        storage: LmdbStorage,
+        _dir: TempDir,
+    }
+
+    impl Temporary {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let storage = open(dir.path());
+            Self { storage, _dir: dir }
+        }
+    }
+
+    impl Fixture for Temporary {
+        type Storage<'a> = Borrowed<'a, LmdbStorage>;
+
+        fn storage(&mut self) -> Self::Storage<'_> {
+            Borrowed(&mut self.storage)
+        }
+    }
+
+    crate::storage_conformance_tests!(Temporary::new);
+
+    mod write_txn {
+        use super::*;
+
+        /// A write transaction over a [`Temporary`] environment, which is never
+        /// committed.
+        struct WriteTxn(Temporary);
+
+        impl Fixture for WriteTxn {
+            type Storage<'a> = LmdbWriteTxn<'a>;
+
+            fn storage(&mut self) -> Self::Storage<'_> {
+                self.0.storage.write_txn().unwrap()
+            }
+        }
+
+        crate::storage_conformance_tests!(|| WriteTxn(Temporary::new()));
+    }
+
+    fn owned(entry: KvResult<'_>) -> (Vec<u8>, Vec<u8>) {
+        let (key, value) = entry.unwrap();
+        (key.into_owned(), value.into_owned())
+    }
+
+    #[test]
+    fn test_iter_pages() {
+        let Temporary { mut storage, _dir } = Temporary::new();
+        let mut batch = WriteBatch::new();
+        for i in 0..2 * PAGE_SIZE as u32 + 1 {
+            batch.put(i.to_be_bytes().to_vec(), i.to_le_bytes().to_vec());
+        }
+        storage.write_batch(batch).unwrap();
+
+        for order in [Order::Ascending, Order::Descending] {
+            let txn = storage.read_txn().unwrap();
+            let expected: Vec<_> = txn.prefix_iter(b"", order).unwrap().map(owned).collect();
+
+            let entries: Vec<_> = storage
+                .prefix_iter(b"", order)
+                .unwrap()
+                .map(owned)
+                .collect();
+            assert_eq!(entries.len(), 2 * PAGE_SIZE + 1);
+            assert_eq!(entries, expected);
+            let keys = storage.prefix_keys(b"", order).unwrap();
+            let keys: Vec<_> = keys.map(|key| key.unwrap().into_owned()).collect();
+            let expected_keys: Vec<_> = expected.into_iter().map(|(key, _)| key).collect();
+            assert_eq!(keys, expected_keys);
+        }
+    }
+
+    #[test]
+    fn test_transactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open(dir.path());
+        let pq: PriorityQueue<u8, String, DisplayEncoding> = PriorityQueue::new(b"pq");
+
+        let mut txn = storage.write_txn().unwrap();
+        pq.push(&mut txn, 2, &"two".to_string()).unwrap();
+        pq.push(&mut txn, 1, &"one".to_string()).unwrap();
+        txn.abort();
+        assert_eq!(storage.get_raw(b"anything"), Ok(None));
+        assert_eq!(
+            pq.peek(&storage.read_txn().unwrap(), Order::Ascending),
+            Ok(None)
+        );
+
+        let mut txn = storage.write_txn().unwrap();
+        pq.push(&mut txn, 2, &"two".to_string()).unwrap();
+        pq.push(&mut txn, 1, &"one".to_string()).unwrap();
+        assert_eq!(
+            pq.pop(&mut txn, Order::Ascending).unwrap(),
+            Some((1, "one".to_string()))
+        );
+        txn.commit().unwrap();
+
+        // Writes through the storage itself commit immediately, and clones share
+        // the environment
+        let read = storage.read_txn().unwrap();
+        storage
+            .clone()
+            .set_raw(b"key".to_vec(), b"value".to_vec())
+            .unwrap();
+        assert_eq!(read.get_raw(b"key"), Ok(None));
+        assert_eq!(
+            pq.peek(&read, Order::Ascending).unwrap(),
+            Some((2, "two".to_string()))
+        );
+        // The storage reads in its own transaction, even while this thread
+        // holds another
+        assert_eq!(
+            storage.get_raw(b"key").unwrap().as_deref(),
+            Some(b"value".as_slice())
+        );
+    }
+}
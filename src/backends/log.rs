@@ -0,0 +1,761 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Bound,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
+
+use super::{
+    crc32,
+    wire::{put_op, Decoder},
+};
+use crate::{
+    storage::is_empty_range, BackendError, BatchOp, Encodable, IterableStorage, KeyEncoding, Order,
+    RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+/// Written at the start of every log file.
+const MAGIC: &[u8; 8] = b"libkvlog";
+/// Every record starts with the length of its payload and the payload's CRC-32,
+/// both as little-endian `u32`s.
+const RECORD_HEADER_LEN: usize = 8;
+
+/// Logs shorter than this are never compacted automatically.
+const AUTO_COMPACT_MIN_LEN: u64 = 1 << 20;
+
+type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Encodes `ops` as a single record, which is replayed all or nothing.
+fn encode_record<'a>(ops: impl IntoIterator<Item = &'a BatchOp>) -> Result<Vec<u8>, BackendError> {
+    let mut record = vec![0; RECORD_HEADER_LEN];
+    for op in ops {
+        put_op(&mut record, op)?;
+    }
+
+    let payload = &record[RECORD_HEADER_LEN..];
+    let len =
+        u32::try_from(payload.len()).map_err(|_| BackendError::new("log record exceeds 4 GiB"))?;
+    let crc = crc32(payload);
+    record[..4].copy_from_slice(&len.to_le_bytes());
+    record[4..RECORD_HEADER_LEN].copy_from_slice(&crc.to_le_bytes());
+    Ok(record)
+}
+
+/// Decodes the payload of a record whose checksum matched. Records are never
+/// empty.
+fn decode_payload(payload: &[u8]) -> Option<Vec<BatchOp>> {
+    if payload.is_empty() {
+        return None;
+    }
+    let mut decoder = Decoder::new(payload);
+    let mut ops = Vec::new();
+    while !decoder.is_empty() {
+        ops.push(decoder.op()?);
+    }
+    Some(ops)
+}
+
+/// The in-memory state of the log: its live entries, and the length they would
+/// take up in a compacted log.
+#[derive(Default)]
+struct Index {
+    entries: Entries,
+    live_len: u64,
+}
+
+/// The length of the record a compacted log stores an entry in.
+fn entry_len(key_len: usize, value_len: usize) -> u64 {
+    // A tag and two lengths precede the key and value
+    (RECORD_HEADER_LEN + 9 + key_len + value_len) as u64
+}
+
+impl Index {
+    fn apply(&mut self, op: BatchOp) -> Result<(), BackendError> {
+        match op {
+            BatchOp::Put(key, value) => {
+                let key_len = key.len();
+                self.live_len += entry_len(key_len, value.len());
+                if let Some(old) = self.entries.insert(key, value) {
+                    self.live_len -= entry_len(key_len, old.len());
+                }
+            }
+            BatchOp::Delete(key) => {
+                if let Some(value) = self.entries.remove(&key) {
+                    self.live_len -= entry_len(key.len(), value.len());
+                }
+            }
+            BatchOp::DeleteRange(low, high) => {
+                if is_empty_range(&low, &high) {
+                    return Ok(());
+                }
+                let removed: u64 = self
+                    .entries
+                    .range((low.clone(), high.clone()))
+                    .map(|(key, value)| entry_len(key.len(), value.len()))
+                    .sum();
+                self.live_len -= removed;
+                self.entries.delete_range_raw(low, high)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `op` would leave every entry unchanged.
+    fn is_noop(&self, op: &BatchOp) -> bool {
+        match op {
+            BatchOp::Put(..) => false,
+            BatchOp::Delete(key) => !self.entries.contains_key(key),
+            BatchOp::DeleteRange(low, high) => {
+                is_empty_range(low, high)
+                    || self
+                        .entries
+                        .range((low.clone(), high.clone()))
+                        .next()
+                        .is_none()
+            }
+        }
+    }
+}
+
+/// Replays the records of a log file into an index. Returns the index and the
+/// length of the log up to the last complete record.
+fn replay(data: &[u8]) -> Result<(Index, usize), BackendError> {
+    let mut index = Index::default();
+    let mut offset = MAGIC.len();
+    while offset < data.len() {
+        let rest = &data[offset..];
+        if rest.len() < RECORD_HEADER_LEN {
+            break;
+        }
+        // Space preallocated by the filesystem may be zeroed past a torn record
+        if rest.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(rest[4..RECORD_HEADER_LEN].try_into().unwrap());
+        let Some(payload) = rest[RECORD_HEADER_LEN..].get(..len) else {
+            // Torn while its payload was being written
+            break;
+        };
+        let end = offset + RECORD_HEADER_LEN + len;
+        let ops = match crc32(payload) == crc {
+            true => decode_payload(payload),
+            false => None,
+        };
+        match ops {
+            Some(ops) => ops.into_iter().try_for_each(|op| index.apply(op))?,
+            // Only the final record can be torn, since records are appended one
+            // at a time
+            None if end == data.len() => break,
+            None => {
+                return Err(BackendError::new(format!(
+                    "corrupt log record at offset {offset}"
+                )))
+            }
+        }
+        offset = end;
+    }
+    Ok((index, offset))
+}
+
+/// Writes a log holding one record per entry to `path`, and syncs it.
+fn write_snapshot(path: &Path, entries: &Entries) -> Result<(), BackendError> {
+    let mut file = BufWriter::new(File::create(path).map_err(BackendError::new)?);
+    file.write_all(MAGIC).map_err(BackendError::new)?;
+    for (key, value) in entries {
+        let record = encode_record([&BatchOp::Put(key.clone(), value.clone())])?;
+        file.write_all(&record).map_err(BackendError::new)?;
+    }
+    let file = file
+        .into_inner()
+        .map_err(|e| BackendError::new(e.into_error()))?;
+    file.sync_all().map_err(BackendError::new)
+}
+
+fn open_log(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)
+}
+
+/// A compaction running on a background thread.
+struct Compaction {
+    /// The length of the log when the compaction started. The records after it
+    /// are copied to the compacted log once it is written.
+    snapshot_len: u64,
+    handle: JoinHandle<Result<(), BackendError>>,
+}
+
+/// A storage backend persisting to a single append-only log file, with no
+/// dependencies beyond `std`.
+///
+/// Every entry is kept in an in-memory `BTreeMap`, which serves all reads and
+/// iteration. Every write, and every [`WriteBatch`] as a whole, is appended to
+/// the log as one checksummed record, and the log is replayed on open. A final
+/// record torn by a crash is discarded, so a batch is recovered all or nothing;
+/// a corrupted record anywhere else fails the open.
+///
+/// Writes reach the operating system before returning, so survive the process
+/// crashing. They are only synced to disk on [`LogStorage::sync`], or on every
+/// write with [`LogStorage::set_sync_writes`].
+///
+/// Overwritten and deleted entries stay in the log until it is compacted, by
+/// rewriting the live entries to a new file which replaces the log. Compaction
+/// runs on demand with [`LogStorage::compact`], or on a background thread with
+/// [`LogStorage::compact_in_background`]. Once the log is at least 1 MiB and
+/// more than twice the size of its live entries, a background compaction starts
+/// automatically, unless disabled with [`LogStorage::set_auto_compaction`]. A
+/// background compaction that fails leaves the log as it was, and its error is
+/// returned by the next [`LogStorage::sync`] or [`LogStorage::compact`], never by
+/// a write.
+pub struct LogStorage {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    index: Index,
+    sync_writes: bool,
+    auto_compaction: bool,
+    /// The log length below which compaction never starts automatically.
+    auto_compact_min_len: u64,
+    compaction: Option<Compaction>,
+    /// The error of a failed background compaction, until it is reported.
+    compaction_error: Option<BackendError>,
+}
+
+impl LogStorage {
+    /// Opens or creates the log at `path`, and replays it.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = open_log(&path).map_err(BackendError::new)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).map_err(BackendError::new)?;
+
+        // A log torn while writing its header holds no records
+        if data.len() < MAGIC.len() && MAGIC.starts_with(&data) {
+            file.set_len(0).map_err(BackendError::new)?;
+            file.write_all(MAGIC).map_err(BackendError::new)?;
+            file.sync_all().map_err(BackendError::new)?;
+            data = MAGIC.to_vec();
+        }
+        if !data.starts_with(MAGIC) {
+            return Err(BackendError::new(format!(
+                "{} is not a libkv log",
+                path.display()
+            )));
+        }
+
+        let (index, len) = replay(&data)?;
+        if len < data.len() {
+            file.set_len(len as u64).map_err(BackendError::new)?;
+        }
+        Ok(Self {
+            path,
+            file,
+            len: len as u64,
+            index,
+            sync_writes: false,
+            auto_compaction: true,
+            auto_compact_min_len: AUTO_COMPACT_MIN_LEN,
+            compaction: None,
+            compaction_error: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The current length of the log file, in bytes.
+    pub fn log_len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether to sync the log to disk after every write. Off by default.
+    pub fn set_sync_writes(&mut self, sync_writes: bool) {
+        self.sync_writes = sync_writes;
+    }
+
+    /// Whether to start background compactions automatically. On by default.
+    pub fn set_auto_compaction(&mut self, auto_compaction: bool) {
+        self.auto_compaction = auto_compaction;
+    }
+
+    /// Syncs the log to disk, then reports a background compaction that failed
+    /// since the last report.
+    pub fn sync(&mut self) -> Result<(), BackendError> {
+        self.file.sync_data().map_err(BackendError::new)?;
+        self.compaction_error.take().map_or(Ok(()), Err)
+    }
+
+    /// Compacts the log, waiting for the compaction to finish. First reports a
+    /// background compaction that failed since the last report, without
+    /// compacting.
+    pub fn compact(&mut self) -> Result<(), BackendError> {
+        self.finish_compaction()?;
+        if let Some(e) = self.compaction_error.take() {
+            return Err(e);
+        }
+        self.compact_in_background();
+        self.finish_compaction()
+    }
+
+    /// Starts compacting the log on a background thread, unless a compaction is
+    /// already running. The compacted log replaces the current one on the first
+    /// write after the compaction finishes, or on [`LogStorage::compact`], or
+    /// when the storage is dropped.
+    ///
+    /// The live entries are cloned for the background thread, so compaction
+    /// briefly doubles the memory they use.
+    pub fn compact_in_background(&mut self) {
+        if self.compaction.is_some() {
+            return;
+        }
+        let path = compaction_path(&self.path);
+        let entries = self.index.entries.clone();
+        self.compaction = Some(Compaction {
+            snapshot_len: self.len,
+            handle: std::thread::spawn(move || write_snapshot(&path, &entries)),
+        });
+    }
+
+    /// Waits for a running compaction, and replaces the log with the compacted
+    /// one.
+    fn finish_compaction(&mut self) -> Result<(), BackendError> {
+        let Some(compaction) = self.compaction.take() else {
+            return Ok(());
+        };
+        let compacted = compaction_path(&self.path);
+        let written = compaction
+            .handle
+            .join()
+            .unwrap_or_else(|_| Err(BackendError::new("log compaction panicked")));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&compacted);
+            return Err(e);
+        }
+        self.replace_log(&compacted, compaction.snapshot_len)
+    }
+
+    /// Appends the records written since `snapshot_len` to the compacted log at
+    /// `compacted`, and moves it over the log. The compacted log is removed if
+    /// it cannot be moved.
+    fn replace_log(&mut self, compacted: &Path, snapshot_len: u64) -> Result<(), BackendError> {
+        let (file, len) = match self.complete_compacted(compacted, snapshot_len) {
+            Ok(completed) => completed,
+            Err(e) => {
+                let _ = fs::remove_file(compacted);
+                return Err(e);
+            }
+        };
+        if let Err(e) = fs::rename(compacted, &self.path) {
+            let _ = fs::remove_file(compacted);
+            return Err(BackendError::new(e));
+        }
+        // The rename unlinked the old log, so later writes must go to the new one
+        self.file = file;
+        self.len = len;
+        sync_parent(&self.path).map_err(BackendError::new)
+    }
+
+    /// Appends the records written since `snapshot_len` to the compacted log at
+    /// `compacted`, and syncs it. Returns the compacted log and its length.
+    fn complete_compacted(
+        &mut self,
+        compacted: &Path,
+        snapshot_len: u64,
+    ) -> Result<(File, u64), BackendError> {
+        let mut tail = Vec::new();
+        self.file
+            .seek(SeekFrom::Start(snapshot_len))
+            .and_then(|_| self.file.read_to_end(&mut tail))
+            .map_err(BackendError::new)?;
+
+        let mut file = open_log(compacted).map_err(BackendError::new)?;
+        file.write_all(&tail)
+            .and_then(|()| file.sync_all())
+            .map_err(BackendError::new)?;
+        let len = file.metadata().map_err(BackendError::new)?.len();
+        Ok((file, len))
+    }
+
+    /// Finishes a background compaction if it is done, keeping its error to be
+    /// reported later, or starts one if the log has grown enough.
+    fn maintain(&mut self) {
+        match &self.compaction {
+            Some(compaction) if compaction.handle.is_finished() => {
+                if let Err(e) = self.finish_compaction() {
+                    self.compaction_error = Some(e);
+                }
+            }
+            Some(_) => {}
+            None => {
+                let live_len = MAGIC.len() as u64 + self.index.live_len;
+                if self.auto_compaction
+                    && self.len >= self.auto_compact_min_len
+                    && self.len > 2 * live_len
+                {
+                    self.compact_in_background();
+                }
+            }
+        }
+    }
+
+    /// Appends `ops` to the log as one record, then applies them to the index.
+    fn append(&mut self, ops: Vec<BatchOp>) -> Result<(), BackendError> {
+        // Deletes of missing keys need not grow the log
+        if ops.iter().all(|op| self.index.is_noop(op)) {
+            return Ok(());
+        }
+        self.maintain();
+
+        let record = encode_record(&ops)?;
+        let written = self
+            .file
+            .write_all(&record)
+            .and_then(|()| match self.sync_writes {
+                true => self.file.sync_data(),
+                false => Ok(()),
+            });
+        if let Err(e) = written {
+            // Drop a partial record, so later records are not appended after it
+            let _ = self.file.set_len(self.len);
+            return Err(BackendError::new(e));
+        }
+        self.len += record.len() as u64;
+
+        ops.into_iter().try_for_each(|op| self.index.apply(op))?;
+        self.maintain();
+        Ok(())
+    }
+}
+
+/// The path compacted logs are written to, before replacing the log at `path`.
+fn compaction_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".compact");
+    PathBuf::from(name)
+}
+
+/// Syncs the directory holding `path`, so that a rename into it is durable.
+fn sync_parent(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = match parent.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => parent,
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+impl Drop for LogStorage {
+    fn drop(&mut self) {
+        let _ = self.finish_compaction();
+    }
+}
+
+impl Storage for LogStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        Storage::get_raw(&self.index.entries, key)
+    }
+}
+
+impl StorageMut for LogStorage {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.append(vec![BatchOp::Put(key, value)])
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.append(vec![BatchOp::Delete(key.to_vec())])
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        self.append(vec![BatchOp::DeleteRange(low, high)])
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        self.append(batch.into_iter().collect())
+    }
+}
+
+impl IterableStorage for LogStorage {
+    type Keys<'a> = <Entries as IterableStorage>::Keys<'a>;
+    type Iter<'a> = <Entries as IterableStorage>::Iter<'a>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        IterableStorage::keys(&self.index.entries, low, high, order)
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        IterableStorage::iter(&self.index.entries, low, high, order)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::TempDir;
+
+    use crate::{
+        mock::DisplayEncoding,
+        testing::{Borrowed, Fixture},
+        Item, Map, Vector,
+    };
+
+    use super::*;
+
+    /// A fresh log, whose directory is removed once it is closed. It compacts
+    /// in the background whenever it holds more stale entries than live ones,
+    /// however small it is.
+    struct Temporary {
+        storage: LogStorage,
+        _dir: TempDir,
+    }
+
+    impl Temporary {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let mut storage = LogStorage::open(dir.path().join("log")).unwrap();
+            storage.auto_compact_min_len = 0;
+            Self { storage, _dir: dir }
+        }
+    }
+
+    impl Fixture for Temporary {
+        type Storage<'a> = Borrowed<'a, LogStorage>;
+
+        fn storage(&mut self) -> Self::Storage<'_> {
+            Borrowed(&mut self.storage)
+        }
+    }
+
+    crate::storage_conformance_tests!(Temporary::new);
+
+    fn contents(storage: &LogStorage) -> Entries {
+        storage.index.entries.clone()
+    }
+
+    #[test]
+    fn test_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        const VECTOR: Vector<Item<u32, DisplayEncoding>> = Vector::new(b"vec");
+        const MAP: Map<u8, Vector<Item<u32, DisplayEncoding>>> = Map::new(b"map");
+
+        let mut storage = LogStorage::open(&path).unwrap();
+        for i in 0..10 {
+            VECTOR.push(&mut storage, &i).unwrap();
+        }
+        VECTOR.truncate(&mut storage, 4).unwrap();
+        for i in 0..3 {
+            MAP.at(i)
+                .unwrap()
+                .push(&mut storage, &u32::from(i))
+                .unwrap();
+        }
+        MAP.remove_prefix(&mut storage, 1).unwrap();
+        storage.delete_raw(b"missing").unwrap();
+        let expected = contents(&storage);
+        drop(storage);
+
+        let storage = LogStorage::open(&path).unwrap();
+        assert_eq!(contents(&storage), expected);
+        assert_eq!(VECTOR.len(&storage), Ok(4));
+        assert_eq!(MAP.at(1).unwrap().len(&storage), Ok(0));
+        assert_eq!(MAP.at(2).unwrap().len(&storage), Ok(1));
+    }
+
+    #[test]
+    fn test_torn_final_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut storage = LogStorage::open(&path).unwrap();
+        storage.set_raw(b"a".to_vec(), b"1".to_vec()).unwrap();
+        let len = storage.log_len();
+        let mut batch = WriteBatch::new();
+        batch.put(b"b".to_vec(), b"2".to_vec());
+        batch.delete(b"a".to_vec());
+        storage.write_batch(batch).unwrap();
+        drop(storage);
+
+        // A record torn anywhere is dropped as a whole, and the log is cut back
+        // to the records before it
+        let data = fs::read(&path).unwrap();
+        let mut zero_filled = data[..len as usize].to_vec();
+        zero_filled.resize(data.len() + 16, 0);
+        let torn_logs = [
+            data[..len as usize + 3].to_vec(),
+            data[..len as usize + RECORD_HEADER_LEN].to_vec(),
+            data[..data.len() - 1].to_vec(),
+            zero_filled,
+        ];
+        for torn in torn_logs {
+            fs::write(&path, torn).unwrap();
+            let mut storage = LogStorage::open(&path).unwrap();
+            assert_eq!(storage.log_len(), len);
+            assert_eq!(
+                contents(&storage),
+                Entries::from([(b"a".to_vec(), b"1".to_vec())])
+            );
+            storage.set_raw(b"b".to_vec(), b"2".to_vec()).unwrap();
+            drop(storage);
+            let storage = LogStorage::open(&path).unwrap();
+            assert_eq!(contents(&storage).len(), 2);
+        }
+        fs::write(&path, &data[..len as usize]).unwrap();
+
+        // So is a final record whose bytes were not all written
+        let mut data = fs::read(&path).unwrap();
+        data.extend_from_slice(&encode_record([&BatchOp::Delete(b"a".to_vec())]).unwrap());
+        *data.last_mut().unwrap() ^= 0xFF;
+        fs::write(&path, &data).unwrap();
+        let storage = LogStorage::open(&path).unwrap();
+        assert_eq!(storage.log_len(), len);
+        assert_eq!(contents(&storage).len(), 1);
+    }
+
+    #[test]
+    fn test_corrupt_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut storage = LogStorage::open(&path).unwrap();
+        storage.set_raw(b"a".to_vec(), b"1".to_vec()).unwrap();
+        storage.set_raw(b"b".to_vec(), b"2".to_vec()).unwrap();
+        drop(storage);
+
+        let mut data = fs::read(&path).unwrap();
+        data[MAGIC.len() + RECORD_HEADER_LEN + 5] ^= 0xFF;
+        fs::write(&path, &data).unwrap();
+        assert!(LogStorage::open(&path).is_err());
+
+        fs::write(&path, b"not a log").unwrap();
+        assert!(LogStorage::open(&path).is_err());
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut storage = LogStorage::open(&path).unwrap();
+        for i in 0..100u32 {
+            storage
+                .set_raw(vec![(i % 10) as u8], i.to_be_bytes().to_vec())
+                .unwrap();
+        }
+        storage.delete_raw(&[0]).unwrap();
+        let expected = contents(&storage);
+        let len = storage.log_len();
+
+        storage.compact().unwrap();
+        assert!(storage.log_len() < len / 5);
+        assert_eq!(
+            storage.log_len(),
+            MAGIC.len() as u64 + storage.index.live_len
+        );
+        assert!(!compaction_path(&path).exists());
+        storage.set_raw(vec![0], vec![0]).unwrap();
+        drop(storage);
+
+        let storage = LogStorage::open(&path).unwrap();
+        let mut expected = expected;
+        expected.insert(vec![0], vec![0]);
+        assert_eq!(contents(&storage), expected);
+    }
+
+    #[test]
+    fn test_compact_in_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut storage = LogStorage::open(&path).unwrap();
+        for i in 0..100u8 {
+            storage.set_raw(vec![i % 10], vec![i]).unwrap();
+        }
+        storage.compact_in_background();
+        // Writes made while compacting are carried over to the compacted log
+        for i in 0..5u8 {
+            storage.set_raw(vec![i], vec![i]).unwrap();
+        }
+        storage
+            .delete_range_raw(Bound::Included(vec![8]), Bound::Unbounded)
+            .unwrap();
+        let expected = contents(&storage);
+        drop(storage);
+
+        let storage = LogStorage::open(&path).unwrap();
+        assert_eq!(contents(&storage), expected);
+        assert!(storage.log_len() < 30 * entry_len(1, 1));
+    }
+
+    #[test]
+    fn test_failed_background_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        // The compacted log cannot be created over a directory
+        fs::create_dir(compaction_path(&path)).unwrap();
+
+        let mut storage = LogStorage::open(&path).unwrap();
+        storage.set_raw(vec![0], vec![0]).unwrap();
+        storage.compact_in_background();
+        while !storage.compaction.as_ref().unwrap().handle.is_finished() {
+            std::thread::yield_now();
+        }
+        // Writes succeed, and the failure is reported once
+        storage.set_raw(vec![1], vec![1]).unwrap();
+        assert!(storage.compaction.is_none());
+        assert!(storage.sync().is_err());
+        storage.sync().unwrap();
+
+        fs::remove_dir(compaction_path(&path)).unwrap();
+        storage.compact().unwrap();
+        let expected = contents(&storage);
+        drop(storage);
+        assert_eq!(contents(&LogStorage::open(&path).unwrap()), expected);
+    }
+
+    #[test]
+    fn test_auto_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+
+        let mut storage = LogStorage::open(&path).unwrap();
+        let value = vec![0; 1024];
+        for i in 0..2048u32 {
+            storage
+                .set_raw((i % 16).to_be_bytes().to_vec(), value.clone())
+                .unwrap();
+        }
+        let expected = contents(&storage);
+        storage.finish_compaction().unwrap();
+        assert!(storage.log_len() < 2048 * 1024);
+        drop(storage);
+
+        let storage = LogStorage::open(&path).unwrap();
+        assert_eq!(contents(&storage), expected);
+    }
+}
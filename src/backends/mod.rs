@@ -0,0 +1,98 @@
+pub(crate) mod concurrent;
+#[cfg(feature = "fjall")]
+pub(crate) mod fjall;
+#[cfg(feature = "lmdb")]
+pub(crate) mod lmdb;
+pub(crate) mod log;
+#[cfg(feature = "redb")]
+pub(crate) mod redb;
+#[cfg(feature = "remote")]
+pub(crate) mod remote;
+#[cfg(feature = "rocksdb")]
+pub(crate) mod rocksdb;
+#[cfg(feature = "sled")]
+pub(crate) mod sled;
+#[cfg(feature = "sqlite")]
+pub(crate) mod sqlite;
+#[cfg(feature = "sstable")]
+pub(crate) mod sstable;
+mod wire;
+
+#[cfg(any(feature = "fjall", feature = "sled"))]
+use std::{collections::BTreeMap, ops::Bound};
+
+#[cfg(any(feature = "fjall", feature = "sled"))]
+use crate::{storage::is_empty_range, BackendError, BatchOp, WriteBatch};
+
+/// Resolves `batch` into the final state of every key it writes: `Some` value to
+/// store, or `None` to delete. For backends whose native batches have no range
+/// delete, range deletes are expanded into deletes of the stored keys `scan`
+/// finds in range, and of the keys written earlier in the batch.
+#[cfg(any(feature = "fjall", feature = "sled"))]
+pub(crate) fn resolve_batch(
+    batch: WriteBatch,
+    mut scan: impl FnMut(&Bound<Vec<u8>>, &Bound<Vec<u8>>) -> Result<Vec<Vec<u8>>, BackendError>,
+) -> Result<BTreeMap<Vec<u8>, Option<Vec<u8>>>, BackendError> {
+    let mut writes = BTreeMap::new();
+    for op in batch {
+        match op {
+            BatchOp::Put(key, value) => {
+                writes.insert(key, Some(value));
+            }
+            BatchOp::Delete(key) => {
+                writes.insert(key, None);
+            }
+            BatchOp::DeleteRange(low, high) => {
+                if is_empty_range(&low, &high) {
+                    continue;
+                }
+                for key in scan(&low, &high)? {
+                    writes.insert(key, None);
+                }
+                for (_, value) in writes.range_mut((low, high)) {
+                    *value = None;
+                }
+            }
+        }
+    }
+    Ok(writes)
+}
+
+/// The CRC-32 (IEEE) lookup table, one entry per byte value.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => 0xEDB8_8320 ^ (crc >> 1),
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// The CRC-32 (IEEE) checksum of `data`, as used by zlib and PNG, for file
+/// formats that detect torn or corrupted records.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
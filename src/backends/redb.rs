@@ -0,0 +1,266 @@
+use std::{borrow::Cow, ops::Bound, path::Path};
+
+use ::redb::{
+    backends::InMemoryBackend, AccessGuard, Database, ReadOnlyTable, StorageError, TableDefinition,
+    TableError,
+};
+
+use crate::{
+    storage::{encode_bound, is_empty_range},
+    BackendError, BatchOp, Directed, Encodable, IterableStorage, KeyEncoding, KeyResult, KvResult,
+    Order, RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+type Table = TableDefinition<'static, &'static [u8], &'static [u8]>;
+type RawTable<'txn> = ::redb::Table<'txn, &'static [u8], &'static [u8]>;
+type ReadTable = ReadOnlyTable<&'static [u8], &'static [u8]>;
+
+/// The table used by [`RedbStorage::new`].
+const DEFAULT_TABLE: Table = TableDefinition::new("libkv");
+
+/// A storage backend over a single table of a redb database.
+///
+/// Every read opens a read transaction, and every write, including a whole
+/// [`WriteBatch`], commits one write transaction. Iterators hold their read
+/// transaction open, and so see a snapshot of the table.
+pub struct RedbStorage {
+    db: Database,
+    table: Table,
+}
+
+impl RedbStorage {
+    /// Uses the `libkv` table of `db`.
+    pub const fn new(db: Database) -> Self {
+        Self::with_table(db, DEFAULT_TABLE)
+    }
+
+    pub const fn with_table(db: Database, table: Table) -> Self {
+        Self { db, table }
+    }
+
+    /// Opens or creates the database at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        Ok(Self::new(
+            Database::create(path).map_err(BackendError::new)?,
+        ))
+    }
+
+    /// Creates a database that lives in memory, and is dropped with it.
+    pub fn in_memory() -> Result<Self, BackendError> {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .map_err(BackendError::new)?;
+        Ok(Self::new(db))
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    pub fn into_inner(self) -> Database {
+        self.db
+    }
+
+    /// Runs `f` on the table in a write transaction, and commits it.
+    fn write(
+        &self,
+        f: impl FnOnce(&mut RawTable<'_>) -> Result<(), StorageError>,
+    ) -> Result<(), BackendError> {
+        let txn = self.db.begin_write().map_err(BackendError::new)?;
+        {
+            let mut table = txn.open_table(self.table).map_err(BackendError::new)?;
+            f(&mut table).map_err(BackendError::new)?;
+        }
+        txn.commit().map_err(BackendError::new)
+    }
+
+    /// Opens the table in a read transaction, or returns `None` if it has never
+    /// been written to.
+    fn read(&self) -> Result<Option<ReadTable>, BackendError> {
+        let txn = self.db.begin_read().map_err(BackendError::new)?;
+        match txn.open_table(self.table) {
+            Ok(table) => Ok(Some(table)),
+            Err(TableError::TableDoesNotExist(_)) => Ok(None),
+            Err(e) => Err(BackendError::new(e)),
+        }
+    }
+}
+
+fn as_slices(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    bound.as_ref().map(Vec::as_slice)
+}
+
+fn delete_range(
+    table: &mut RawTable<'_>,
+    low: &Bound<Vec<u8>>,
+    high: &Bound<Vec<u8>>,
+) -> Result<(), StorageError> {
+    if is_empty_range(low, high) {
+        return Ok(());
+    }
+    table.retain_in::<&[u8], _>((as_slices(low), as_slices(high)), |_, _| false)
+}
+
+impl Storage for RedbStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        let Some(table) = self.read()? else {
+            return Ok(None);
+        };
+        let value = table.get(key).map_err(BackendError::new)?;
+        Ok(value.map(|value| Cow::Owned(value.value().to_vec())))
+    }
+}
+
+impl StorageMut for RedbStorage {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.write(|table| table.insert(key.as_slice(), value.as_slice()).map(drop))
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.write(|table| table.remove(key).map(drop))
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        self.write(|table| delete_range(table, &low, &high))
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        self.write(|table| {
+            for op in batch {
+                match op {
+                    BatchOp::Put(key, value) => {
+                        table.insert(key.as_slice(), value.as_slice())?;
+                    }
+                    BatchOp::Delete(key) => {
+                        table.remove(key.as_slice())?;
+                    }
+                    BatchOp::DeleteRange(low, high) => delete_range(table, &low, &high)?,
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+type Guard = AccessGuard<'static, &'static [u8]>;
+type RedbEntry = Result<(Guard, Guard), StorageError>;
+type RedbRange = ::redb::Range<'static, &'static [u8], &'static [u8]>;
+
+fn owned_kv<'a>(entry: RedbEntry) -> KvResult<'a> {
+    let (key, value) = entry.map_err(BackendError::new)?;
+    Ok((
+        Cow::Owned(key.value().to_vec()),
+        Cow::Owned(value.value().to_vec()),
+    ))
+}
+
+fn owned_k<'a>(entry: RedbEntry) -> KeyResult<'a> {
+    let (key, _) = entry.map_err(BackendError::new)?;
+    Ok(Cow::Owned(key.value().to_vec()))
+}
+
+impl RedbStorage {
+    fn range(
+        &self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<Option<RedbRange>, BackendError> {
+        if is_empty_range(&low, &high) {
+            return Ok(None);
+        }
+        let Some(table) = self.read()? else {
+            return Ok(None);
+        };
+        let range = table
+            .range::<&[u8]>((as_slices(&low), as_slices(&high)))
+            .map_err(BackendError::new)?;
+        Ok(Some(range))
+    }
+}
+
+impl IterableStorage for RedbStorage {
+    type Keys<'a> = Directed<std::iter::Map<RedbRange, fn(RedbEntry) -> KeyResult<'a>>>;
+    type Iter<'a> = Directed<std::iter::Map<RedbRange, fn(RedbEntry) -> KvResult<'a>>>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        match self.range(encode_bound!(low), encode_bound!(high))? {
+            Some(range) => Ok(Directed::new(range.map(owned_k as fn(_) -> _), order)),
+            None => Ok(Directed::empty(order)),
+        }
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        match self.range(encode_bound!(low), encode_bound!(high))? {
+            Some(range) => Ok(Directed::new(range.map(owned_kv as fn(_) -> _), order)),
+            None => Ok(Directed::empty(order)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{mock::DisplayEncoding, Item, Map, Order};
+
+    use super::*;
+
+    crate::storage_conformance_tests!(|| RedbStorage::in_memory().unwrap());
+
+    #[test]
+    fn test_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("libkv.redb");
+        const MAP: Map<u8, Item<String, DisplayEncoding>> = Map::new(b"map");
+        {
+            let mut storage = RedbStorage::create(&path).unwrap();
+            MAP.at(1)
+                .unwrap()
+                .save(&mut storage, &"one".to_string())
+                .unwrap();
+            MAP.at(2)
+                .unwrap()
+                .save(&mut storage, &"two".to_string())
+                .unwrap();
+        }
+
+        let storage = RedbStorage::create(&path).unwrap();
+        let values: Vec<_> = MAP
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Descending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(values, vec!["two", "one"]);
+    }
+
+    #[test]
+    fn test_iter_is_snapshot() {
+        let mut storage = RedbStorage::in_memory().unwrap();
+        storage.set_raw(b"a".to_vec(), b"1".to_vec()).unwrap();
+        let (low, high) = (Bound::<Vec<u8>>::Unbounded, Bound::Unbounded);
+        let range = storage.range(low.clone(), high.clone()).unwrap().unwrap();
+
+        // The open iterator keeps its read transaction, while writes commit
+        let mut other = RedbStorage::new(storage.db);
+        other.set_raw(b"b".to_vec(), b"2".to_vec()).unwrap();
+        assert_eq!(range.count(), 1);
+        assert_eq!(other.range(low, high).unwrap().unwrap().count(), 2);
+    }
+}
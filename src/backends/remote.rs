@@ -0,0 +1,660 @@
+use std::{
+    borrow::Cow,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    ops::Bound,
+    sync::{Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use super::wire::{put_bound, put_bytes, put_op, Decoder};
+use crate::{
+    storage::{encode_bound, key_of, Page, PagedScan},
+    BackendError, BatchOp, Encodable, IterableStorage, KeyEncoding, KeyResult, KeyType, KvResult,
+    Order, RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+// Every request and response is a frame: the length of its payload as a
+// little-endian `u32`, then the payload. A request payload starts with its
+// kind, and a response payload with its status.
+
+/// Frames longer than this are refused, so that a bad length cannot make
+/// either side allocate without bound.
+const MAX_FRAME_LEN: u32 = 1 << 28;
+
+/// Request `[GET][key]`, answered by `[found u8][value]`.
+const GET: u8 = 0;
+/// Request `[WRITE][op]*`, applied as a single batch, answered by nothing.
+const WRITE: u8 = 1;
+/// Request `[SCAN][low][high][order u8][limit u32][keys only u8]`, answered by
+/// `[count u32]([key][value])*[finished u8]`, without values if keys only.
+const SCAN: u8 = 2;
+
+const OK: u8 = 0;
+/// Followed by the error message, as UTF-8.
+const ERROR: u8 = 1;
+
+const ASCENDING: u8 = 0;
+const DESCENDING: u8 = 1;
+
+/// The server stops filling a page of a scan once it holds this many bytes.
+const PAGE_BYTES: usize = 1 << 20;
+/// Entries requested per page of a scan, unless set otherwise.
+const DEFAULT_PAGE_SIZE: u32 = 256;
+/// How long the server waits to accept connections again after running out of
+/// resources, such as file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+fn write_frame(writer: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame exceeds 256 MiB"))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Reads a frame, or `None` if the stream ends where a frame would start.
+fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame exceeds 256 MiB",
+        ));
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+fn backend_error(e: RawStorageError) -> BackendError {
+    match e {
+        RawStorageError::KeySerialize(e) => match e {},
+        RawStorageError::Backend(e) => e,
+    }
+}
+
+/// Appends up to `limit` items of `iter` to `response`, encoded by `put`,
+/// followed by whether the scan is finished.
+fn put_page<T>(
+    response: &mut Vec<u8>,
+    iter: impl Iterator<Item = Result<T, BackendError>>,
+    limit: u32,
+    mut put: impl FnMut(&mut Vec<u8>, T) -> Result<(), BackendError>,
+) -> Result<(), BackendError> {
+    let start = response.len();
+    response.extend_from_slice(&[0; 4]);
+    let mut iter = iter.peekable();
+    let mut count = 0u32;
+    while count < limit && response.len() - start < PAGE_BYTES {
+        match iter.next() {
+            Some(item) => put(response, item?)?,
+            None => break,
+        }
+        count += 1;
+    }
+    response[start..start + 4].copy_from_slice(&count.to_le_bytes());
+    response.push(iter.peek().is_none() as u8);
+    Ok(())
+}
+
+/// Whether accepting failed because the listener is unusable, rather than for a
+/// single connection: it is not listening, or is non-blocking.
+fn is_listener_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Whether accepting failed because of the connection being accepted, which
+/// retrying right away does not repeat.
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::PermissionDenied
+    )
+}
+
+/// Serves a storage backend to [`RemoteStorage`] clients over TCP.
+///
+/// Each connection is served on its own thread. Requests are applied one at a
+/// time under a lock around the backend, so each request, including a batch,
+/// is atomic with respect to the others. Scans are served a page at a time, so
+/// a client's iterator sees writes made between its pages.
+///
+/// The protocol has no authentication or encryption: only listen on trusted
+/// networks, such as the loopback interface.
+pub struct RemoteServer<S> {
+    storage: Mutex<S>,
+}
+
+impl<S: StorageMut + IterableStorage + Send> RemoteServer<S> {
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage: Mutex::new(storage),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Accepts connections on `listener`, serving each on its own thread, until
+    /// the listener itself fails. Failures to accept a single connection, such
+    /// as a client aborting it or the process running out of file descriptors,
+    /// are passed to `on_accept_error` and skipped. Returns once every
+    /// connection has been closed.
+    ///
+    /// `listener` must be in blocking mode.
+    pub fn serve(
+        &self,
+        listener: &TcpListener,
+        mut on_accept_error: impl FnMut(io::Error),
+    ) -> io::Result<()> {
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) if is_listener_error(&e) => return Err(e),
+                    Err(e) => {
+                        let backoff = !is_connection_error(&e);
+                        on_accept_error(e);
+                        if backoff {
+                            // Wait for resources to be freed rather than spin
+                            thread::sleep(ACCEPT_BACKOFF);
+                        }
+                        continue;
+                    }
+                };
+                // A failed connection only affects its own client
+                scope.spawn(move || self.handle(stream));
+            }
+            Ok(())
+        })
+    }
+
+    /// Serves the requests of a single connection until the client
+    /// disconnects.
+    pub fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        while let Some(request) = read_frame(&mut reader)? {
+            let response = self.respond(&request).unwrap_or_else(|e| {
+                let mut response = vec![ERROR];
+                response.extend_from_slice(e.to_string().as_bytes());
+                response
+            });
+            write_frame(&mut writer, &response)?;
+        }
+        Ok(())
+    }
+
+    fn respond(&self, request: &[u8]) -> Result<Vec<u8>, BackendError> {
+        let malformed = || BackendError::new("malformed request");
+        let mut decoder = Decoder::new(request);
+        let mut response = vec![OK];
+        let mut storage = self.storage.lock().unwrap_or_else(PoisonError::into_inner);
+        match decoder.u8().ok_or_else(malformed)? {
+            GET => {
+                let key = decoder.bytes().ok_or_else(malformed)?;
+                if !decoder.is_empty() {
+                    return Err(malformed());
+                }
+                match storage.get_raw(&key)? {
+                    Some(value) => {
+                        response.push(1);
+                        put_bytes(&mut response, &value)?;
+                    }
+                    None => response.push(0),
+                }
+            }
+            WRITE => {
+                let mut batch = WriteBatch::new();
+                while !decoder.is_empty() {
+                    match decoder.op().ok_or_else(malformed)? {
+                        BatchOp::Put(key, value) => batch.put(key, value),
+                        BatchOp::Delete(key) => batch.delete(key),
+                        BatchOp::DeleteRange(low, high) => batch.delete_range(low, high),
+                    }
+                }
+                storage.write_batch(batch)?;
+            }
+            SCAN => {
+                let low = decoder.bound().ok_or_else(malformed)?;
+                let high = decoder.bound().ok_or_else(malformed)?;
+                let order = match decoder.u8() {
+                    Some(ASCENDING) => Order::Ascending,
+                    Some(DESCENDING) => Order::Descending,
+                    _ => return Err(malformed()),
+                };
+                let limit = decoder.u32().filter(|limit| *limit > 0);
+                let limit = limit.ok_or_else(malformed)?;
+                let keys_only = match decoder.u8() {
+                    Some(keys_only @ (0 | 1)) => keys_only == 1,
+                    _ => return Err(malformed()),
+                };
+                if !decoder.is_empty() {
+                    return Err(malformed());
+                }
+
+                let (low, high) = (low.map(KeyType::<()>::Raw), high.map(KeyType::Raw));
+                if keys_only {
+                    let keys = storage.keys(low, high, order).map_err(backend_error)?;
+                    put_page(&mut response, keys, limit, |response, key| {
+                        put_bytes(response, &key)
+                    })?;
+                } else {
+                    let iter = storage.iter(low, high, order).map_err(backend_error)?;
+                    put_page(&mut response, iter, limit, |response, (key, value)| {
+                        put_bytes(response, &key)?;
+                        put_bytes(response, &value)
+                    })?;
+                }
+            }
+            _ => return Err(malformed()),
+        }
+        Ok(response)
+    }
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    /// Set once a round trip fails, after which the stream may be mid-frame.
+    broken: bool,
+}
+
+impl Connection {
+    fn round_trip(&mut self, request: &[u8]) -> io::Result<Vec<u8>> {
+        write_frame(&mut self.writer, request)?;
+        read_frame(&mut self.reader)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "server closed the connection")
+        })
+    }
+}
+
+fn malformed_response() -> BackendError {
+    BackendError::new("malformed response")
+}
+
+/// A storage backend served by a [`RemoteServer`] over TCP.
+///
+/// Every operation is a round trip to the server over a single connection;
+/// connect several clients to issue requests in parallel. Batches are sent as
+/// a single request, so they are applied atomically if the served backend
+/// applies its batches atomically.
+///
+/// Iterators fetch entries a page at a time, as they advance, so they see
+/// writes made by other clients between pages. Once a round trip fails, the
+/// connection is closed and every later operation fails.
+pub struct RemoteStorage {
+    connection: Mutex<Connection>,
+    page_size: u32,
+}
+
+impl RemoteStorage {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, BackendError> {
+        let connect = || {
+            let stream = TcpStream::connect(addr)?;
+            stream.set_nodelay(true)?;
+            Ok::<_, io::Error>(Connection {
+                reader: BufReader::new(stream.try_clone()?),
+                writer: BufWriter::new(stream),
+                broken: false,
+            })
+        };
+        Ok(Self {
+            connection: Mutex::new(connect().map_err(BackendError::new)?),
+            page_size: DEFAULT_PAGE_SIZE,
+        })
+    }
+
+    /// Sets how many entries iterators fetch per round trip, 256 by default.
+    pub fn set_page_size(&mut self, page_size: u32) {
+        self.page_size = page_size.max(1);
+    }
+
+    /// Sends `request`, returning the body of a successful response.
+    fn call(&self, request: &[u8]) -> Result<Vec<u8>, BackendError> {
+        let mut connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if connection.broken {
+            return Err(BackendError::new("connection to the server was lost"));
+        }
+        let response = connection.round_trip(request);
+        connection.broken = response.is_err();
+        let mut response = response.map_err(BackendError::new)?;
+        match response.first() {
+            Some(&OK) => {
+                response.remove(0);
+                Ok(response)
+            }
+            Some(&ERROR) => Err(BackendError::new(
+                String::from_utf8_lossy(&response[1..]).into_owned(),
+            )),
+            _ => Err(malformed_response()),
+        }
+    }
+
+    fn write<'a>(&self, ops: impl IntoIterator<Item = &'a BatchOp>) -> Result<(), BackendError> {
+        let mut request = vec![WRITE];
+        for op in ops {
+            put_op(&mut request, op)?;
+        }
+        self.call(&request)?;
+        Ok(())
+    }
+
+    fn scan(
+        &self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+        order: Order,
+        keys_only: bool,
+    ) -> RemoteIter<'_> {
+        RemoteIter {
+            storage: self,
+            keys_only,
+            scan: PagedScan::new(low, high, order),
+        }
+    }
+
+    /// Fetches a page of the entries between `low` and `high`, in `order`, in a
+    /// single round trip.
+    fn fetch(
+        &self,
+        low: &Bound<Vec<u8>>,
+        high: &Bound<Vec<u8>>,
+        order: Order,
+        keys_only: bool,
+    ) -> Result<(Page, bool), BackendError> {
+        let mut request = vec![SCAN];
+        put_bound(&mut request, low)?;
+        put_bound(&mut request, high)?;
+        request.push(match order {
+            Order::Ascending => ASCENDING,
+            Order::Descending => DESCENDING,
+        });
+        request.extend_from_slice(&self.page_size.to_le_bytes());
+        request.push(keys_only as u8);
+
+        let response = self.call(&request)?;
+        let mut decoder = Decoder::new(&response);
+        let count = decoder.u32().ok_or_else(malformed_response)?;
+        let mut page = Vec::new();
+        for _ in 0..count {
+            let key = decoder.bytes().ok_or_else(malformed_response)?;
+            let value = match keys_only {
+                true => Vec::new(),
+                false => decoder.bytes().ok_or_else(malformed_response)?,
+            };
+            page.push((key, value));
+        }
+        let last = decoder.u8().ok_or_else(malformed_response)? != 0;
+        if !decoder.is_empty() || (page.is_empty() && !last) {
+            return Err(malformed_response());
+        }
+        Ok((page, last))
+    }
+}
+
+impl Storage for RemoteStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        let mut request = vec![GET];
+        put_bytes(&mut request, key)?;
+        let response = self.call(&request)?;
+        let mut decoder = Decoder::new(&response);
+        let value = match decoder.u8() {
+            Some(0) => None,
+            Some(1) => Some(decoder.bytes().ok_or_else(malformed_response)?),
+            _ => return Err(malformed_response()),
+        };
+        Ok(value.map(Cow::Owned))
+    }
+}
+
+impl StorageMut for RemoteStorage {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.write(&[BatchOp::Put(key, value)])
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.write(&[BatchOp::Delete(key.to_vec())])
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        self.write(&[BatchOp::DeleteRange(low, high)])
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.write(&batch)
+    }
+}
+
+/// Iterator over a range of a [`RemoteStorage`], fetching a page of entries
+/// per round trip.
+pub struct RemoteIter<'a> {
+    storage: &'a RemoteStorage,
+    keys_only: bool,
+    scan: PagedScan,
+}
+
+impl<'a> Iterator for RemoteIter<'a> {
+    type Item = KvResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (storage, keys_only) = (self.storage, self.keys_only);
+        self.scan
+            .next(|low, high, order| storage.fetch(low, high, order, keys_only))
+    }
+}
+
+impl IterableStorage for RemoteStorage {
+    type Keys<'a> = std::iter::Map<RemoteIter<'a>, fn(KvResult<'a>) -> KeyResult<'a>>;
+    type Iter<'a> = RemoteIter<'a>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        let iter = self.scan(encode_bound!(low), encode_bound!(high), order, true);
+        Ok(iter.map(key_of as fn(_) -> _))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        Ok(self.scan(encode_bound!(low), encode_bound!(high), order, false))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
+
+    use crate::{mock::FailingStorage, KeyType};
+
+    use super::*;
+
+    type Memory = BTreeMap<Vec<u8>, Vec<u8>>;
+
+    /// Starts a server over `storage` on a free loopback port.
+    fn start<S: StorageMut + IterableStorage + Send + 'static>(
+        storage: S,
+    ) -> (Arc<RemoteServer<S>>, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Arc::new(RemoteServer::new(storage));
+        let serving = server.clone();
+        thread::spawn(move || serving.serve(&listener, drop));
+        (server, addr)
+    }
+
+    /// Connects to a server serving this connection alone, which stops once
+    /// the client is dropped.
+    fn temporary() -> RemoteStorage {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            RemoteServer::new(Memory::new()).handle(stream)
+        });
+        let mut storage = RemoteStorage::connect(addr).unwrap();
+        // Small pages, so that scans span several round trips
+        storage.set_page_size(3);
+        storage
+    }
+
+    crate::storage_conformance_tests!(temporary);
+
+    fn all() -> (Bound<KeyType<()>>, Bound<KeyType<()>>) {
+        (Bound::Unbounded, Bound::Unbounded)
+    }
+
+    #[test]
+    fn test_shared_server() {
+        let (_server, addr) = start(Memory::new());
+        let mut writer = RemoteStorage::connect(addr).unwrap();
+        let mut reader = RemoteStorage::connect(addr).unwrap();
+        reader.set_page_size(2);
+
+        for i in 0..10u8 {
+            writer.set_raw(vec![i], vec![i]).unwrap();
+        }
+        assert_eq!(reader.get_raw(&[3]), Ok(Some(Cow::Owned(vec![3]))));
+
+        // Writes between pages are seen by the rest of the scan
+        let (low, high) = all();
+        let mut iter = reader.iter(low, high, Order::Descending).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().0.as_ref(), [9]);
+        writer.delete_raw(&[7]).unwrap();
+        writer.set_raw(vec![5, 0], vec![]).unwrap();
+        let keys: Vec<_> = iter.map(|entry| entry.unwrap().0.into_owned()).collect();
+        assert_eq!(keys[..4], [vec![8], vec![6], vec![5, 0], vec![5]]);
+        assert_eq!(keys.len(), 9);
+
+        let mut batch = WriteBatch::new();
+        batch.delete_range(Bound::Included(vec![2]), Bound::Unbounded);
+        batch.put(vec![4], vec![4]);
+        writer.write_batch(batch).unwrap();
+        let (low, high) = all();
+        let keys: Vec<_> = reader
+            .keys(low, high, Order::Ascending)
+            .unwrap()
+            .map(|key| key.unwrap().into_owned())
+            .collect();
+        assert_eq!(keys, [vec![0], vec![1], vec![4]]);
+    }
+
+    #[test]
+    fn test_backend_error() {
+        let (server, addr) = start(FailingStorage::default());
+        let mut storage = RemoteStorage::connect(addr).unwrap();
+        storage.set_raw(b"a".to_vec(), b"a".to_vec()).unwrap();
+        storage.set_raw(b"b".to_vec(), b"b".to_vec()).unwrap();
+
+        server.storage.lock().unwrap().failing = true;
+        let error = BackendError::new("disk on fire");
+        assert_eq!(storage.get_raw(b"a"), Err(error));
+        let (low, high) = all();
+        let mut iter = storage.iter(low, high, Order::Ascending).unwrap();
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+
+        // Backend errors leave the connection usable
+        server.storage.lock().unwrap().failing = false;
+        assert_eq!(storage.get_raw(b"b"), Ok(Some(Cow::Owned(b"b".to_vec()))));
+    }
+
+    #[test]
+    fn test_malformed_request() {
+        let (_server, addr) = start(Memory::new());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        for request in [&[][..], &[GET, 1, 0], &[SCAN, 0, 0, 0, 0, 0, 0, 0, 0], &[9]] {
+            write_frame(&mut stream, request).unwrap();
+            let response = read_frame(&mut stream).unwrap().unwrap();
+            assert_eq!(response, b"\x01malformed request");
+        }
+
+        let mut request = vec![GET];
+        put_bytes(&mut request, b"missing").unwrap();
+        write_frame(&mut stream, &request).unwrap();
+        assert_eq!(read_frame(&mut stream).unwrap().unwrap(), [OK, 0]);
+
+        // An oversized frame closes the connection
+        stream.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        assert_eq!(read_frame(&mut stream).unwrap(), None);
+    }
+
+    #[test]
+    fn test_accept_errors() {
+        for kind in [
+            io::ErrorKind::ConnectionAborted,
+            io::ErrorKind::Interrupted,
+            io::ErrorKind::OutOfMemory,
+        ] {
+            assert!(!is_listener_error(&kind.into()), "{kind:?}");
+        }
+        // Out of file descriptors
+        assert!(!is_listener_error(&io::Error::from_raw_os_error(24)));
+        assert!(is_listener_error(&io::ErrorKind::InvalidInput.into()));
+
+        // A non-blocking listener would have `serve` spin
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let server = RemoteServer::new(Memory::new());
+        let error = server.serve(&listener, drop).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn test_server_gone() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Answer one request, then hang up
+            read_frame(&mut stream).unwrap();
+            write_frame(&mut stream, &[OK, 0]).unwrap();
+        });
+        let storage = RemoteStorage::connect(addr).unwrap();
+        assert_eq!(storage.get_raw(b"key"), Ok(None));
+        server.join().unwrap();
+
+        assert!(storage.get_raw(b"key").is_err());
+        assert_eq!(
+            storage.get_raw(b"key"),
+            Err(BackendError::new("connection to the server was lost"))
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    ops::Bound,
+    path::Path,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use ::rocksdb::{
+    BoundColumnFamily, DBRawIteratorWithThreadMode, DBWithThreadMode, MultiThreaded, Options,
+    ReadOptions, DEFAULT_COLUMN_FAMILY_NAME,
+};
+
+use crate::{
+    storage::{encode_bound, is_empty_range, key_of},
+    BackendError, BatchOp, Encodable, IterableStorage, KeyEncoding, KeyResult, KvResult, Order,
+    RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+type Db = DBWithThreadMode<MultiThreaded>;
+
+/// The first key after `key`.
+fn successor(mut key: Vec<u8>) -> Vec<u8> {
+    key.push(0);
+    key
+}
+
+/// The inclusive start of a range, as RocksDB takes it.
+fn lower_key(low: Bound<Vec<u8>>) -> Vec<u8> {
+    match low {
+        Bound::Included(key) => key,
+        Bound::Excluded(key) => successor(key),
+        Bound::Unbounded => Vec::new(),
+    }
+}
+
+/// The exclusive end of a range, as RocksDB takes it, or `None` if unbounded.
+fn upper_key(high: Bound<Vec<u8>>) -> Option<Vec<u8>> {
+    match high {
+        Bound::Included(key) => Some(successor(key)),
+        Bound::Excluded(key) => Some(key),
+        Bound::Unbounded => None,
+    }
+}
+
+/// A storage backend over a single column family of a RocksDB database.
+///
+/// Batches are committed atomically with RocksDB's own write batches, and
+/// range deletes are native range tombstones. Iterators read from a snapshot
+/// taken when they are created.
+///
+/// The database is shared: [`RocksDbStorage::column_family`] returns a storage
+/// over another column family of the same database, which acts as a separate
+/// namespace.
+///
+/// RocksDB range tombstones need an end key, so a range delete without an upper
+/// bound ends just past the last key stored when the batch is written. Handles
+/// cloned from this one, or returned by [`RocksDbStorage::column_family`], hold
+/// off their writes while such a batch is resolved and written. Writes made
+/// directly through [`RocksDbStorage::database`], or through a handle from a
+/// separate call to [`RocksDbStorage::new`], are not held off, and may land
+/// past the end of the delete and survive it.
+#[derive(Clone)]
+pub struct RocksDbStorage {
+    db: Arc<Db>,
+    cf: String,
+    /// Shared by the handles on the database. Writes hold it shared, and
+    /// batches with an unbounded range delete hold it exclusively.
+    writes: Arc<RwLock<()>>,
+}
+
+impl RocksDbStorage {
+    /// Opens or creates the database at `path`, with all of its column
+    /// families, using the default column family.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        Self::open_with(&opts, path)
+    }
+
+    /// Opens the database at `path` with `opts`, with all of its column
+    /// families, using the default column family.
+    pub fn open_with(opts: &Options, path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        // Listing fails for a database that does not exist yet
+        let cfs = Db::list_cf(opts, path.as_ref())
+            .unwrap_or_else(|_| vec![DEFAULT_COLUMN_FAMILY_NAME.to_string()]);
+        let db = Db::open_cf(opts, path, cfs).map_err(BackendError::new)?;
+        Ok(Self::new(Arc::new(db)))
+    }
+
+    /// Uses the default column family of `db`, which must have been opened
+    /// with it listed.
+    pub fn new(db: Arc<Db>) -> Self {
+        Self {
+            db,
+            cf: DEFAULT_COLUMN_FAMILY_NAME.to_string(),
+            writes: Arc::default(),
+        }
+    }
+
+    /// Returns a storage over the column family `name` of the same database,
+    /// creating it if missing.
+    pub fn column_family(&self, name: &str) -> Result<Self, BackendError> {
+        if self.db.cf_handle(name).is_none() {
+            self.db
+                .create_cf(name, &Options::default())
+                .map_err(BackendError::new)?;
+        }
+        Ok(Self {
+            db: self.db.clone(),
+            cf: name.to_string(),
+            writes: self.writes.clone(),
+        })
+    }
+
+    pub fn database(&self) -> &Arc<Db> {
+        &self.db
+    }
+
+    fn cf(&self) -> Result<Arc<BoundColumnFamily<'_>>, BackendError> {
+        self.db
+            .cf_handle(&self.cf)
+            .ok_or_else(|| BackendError::new(format!("column family {:?} does not exist", self.cf)))
+    }
+
+    /// Returns the last stored key at or after `low`.
+    fn last_key(
+        &self,
+        cf: &Arc<BoundColumnFamily<'_>>,
+        low: &[u8],
+    ) -> Result<Option<Vec<u8>>, BackendError> {
+        let mut opts = ReadOptions::default();
+        opts.set_iterate_lower_bound(low);
+        let mut iter = self.db.raw_iterator_cf_opt(cf, opts);
+        iter.seek_to_last();
+        iter.status().map_err(BackendError::new)?;
+        Ok(iter.key().map(<[u8]>::to_vec))
+    }
+}
+
+impl Storage for RocksDbStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        let value = self
+            .db
+            .get_pinned_cf(&self.cf()?, key)
+            .map_err(BackendError::new)?;
+        Ok(value.map(|value| Cow::Owned(value.to_vec())))
+    }
+}
+
+impl StorageMut for RocksDbStorage {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        let _shared = self.writes.read().unwrap_or_else(PoisonError::into_inner);
+        self.db
+            .put_cf(&self.cf()?, key, value)
+            .map_err(BackendError::new)
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        let _shared = self.writes.read().unwrap_or_else(PoisonError::into_inner);
+        self.db
+            .delete_cf(&self.cf()?, key)
+            .map_err(BackendError::new)
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        let mut batch = WriteBatch::new();
+        batch.delete_range(low, high);
+        self.write_batch(batch)
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        // No other handle may write past the end an unbounded delete resolves
+        // to before the batch is written
+        let unbounded = batch
+            .iter()
+            .any(|op| matches!(op, BatchOp::DeleteRange(_, Bound::Unbounded)));
+        let (_shared, _exclusive);
+        if unbounded {
+            _exclusive = self.writes.write().unwrap_or_else(PoisonError::into_inner);
+        } else {
+            _shared = self.writes.read().unwrap_or_else(PoisonError::into_inner);
+        }
+
+        let cf = self.cf()?;
+        let mut rocks_batch = ::rocksdb::WriteBatch::default();
+        // Keys put earlier in the batch, which a range delete without an end
+        // must still cover
+        let mut puts = BTreeSet::new();
+        for op in batch {
+            match op {
+                BatchOp::Put(key, value) => {
+                    rocks_batch.put_cf(&cf, &key, value);
+                    puts.insert(key);
+                }
+                BatchOp::Delete(key) => rocks_batch.delete_cf(&cf, key),
+                BatchOp::DeleteRange(low, high) => {
+                    if is_empty_range(&low, &high) {
+                        continue;
+                    }
+                    let from = lower_key(low);
+                    // RocksDB needs an end for range tombstones, so an unbounded
+                    // delete ends just after the last key it can reach
+                    let to = match upper_key(high) {
+                        Some(to) => to,
+                        None => {
+                            let last_stored = self.last_key(&cf, &from)?;
+                            let last_put = puts.last().filter(|key| **key >= from).cloned();
+                            match last_stored.max(last_put) {
+                                Some(last) => successor(last),
+                                None => continue,
+                            }
+                        }
+                    };
+                    if from < to {
+                        rocks_batch.delete_range_cf(&cf, from, to);
+                    }
+                }
+            }
+        }
+        self.db.write(rocks_batch).map_err(BackendError::new)
+    }
+}
+
+/// Iterator over a range of a [`RocksDbStorage`], in either [`Order`].
+pub struct RocksDbIter<'a> {
+    iter: DBRawIteratorWithThreadMode<'a, Db>,
+    order: Order,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> Iterator for RocksDbIter<'a> {
+    type Item = KvResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match (self.started, self.order) {
+            (false, Order::Ascending) => self.iter.seek_to_first(),
+            (false, Order::Descending) => self.iter.seek_to_last(),
+            (true, Order::Ascending) => self.iter.next(),
+            (true, Order::Descending) => self.iter.prev(),
+        }
+        self.started = true;
+        match self.iter.item() {
+            Some((key, value)) => Some(Ok((Cow::Owned(key.to_vec()), Cow::Owned(value.to_vec())))),
+            None => {
+                self.done = true;
+                self.iter.status().err().map(|e| Err(BackendError::new(e)))
+            }
+        }
+    }
+}
+
+impl RocksDbStorage {
+    fn range(
+        &self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+        order: Order,
+    ) -> Result<RocksDbIter<'_>, BackendError> {
+        let done = is_empty_range(&low, &high);
+        let mut opts = ReadOptions::default();
+        opts.set_iterate_lower_bound(lower_key(low));
+        if let Some(high) = upper_key(high) {
+            opts.set_iterate_upper_bound(high);
+        }
+        Ok(RocksDbIter {
+            iter: self.db.raw_iterator_cf_opt(&self.cf()?, opts),
+            order,
+            started: false,
+            done,
+        })
+    }
+}
+
+impl IterableStorage for RocksDbStorage {
+    type Keys<'a> = std::iter::Map<RocksDbIter<'a>, fn(KvResult<'a>) -> KeyResult<'a>>;
+    type Iter<'a> = RocksDbIter<'a>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        let iter = self.iter(low, high, order)?;
+        Ok(iter.map(key_of as fn(_) -> _))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        Ok(self.range(encode_bound!(low), encode_bound!(high), order)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::rocksdb::Env;
+
+    use crate::{mock::DisplayEncoding, Item, Map};
+
+    use super::*;
+
+    fn temporary() -> RocksDbStorage {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.set_env(&Env::mem_env().unwrap());
+        RocksDbStorage::open_with(&opts, "libkv").unwrap()
+    }
+
+    crate::storage_conformance_tests!(temporary);
+
+    #[test]
+    fn test_column_families() {
+        let dir = tempfile::tempdir().unwrap();
+        const MAP: Map<u8, Item<String, DisplayEncoding>> = Map::new(b"map");
+        {
+            let mut storage = RocksDbStorage::open(dir.path()).unwrap();
+            let mut other = storage.column_family("other").unwrap();
+            MAP.at(1)
+                .unwrap()
+                .save(&mut storage, &"default".to_string())
+                .unwrap();
+            for key in [1, 2] {
+                MAP.at(key)
+                    .unwrap()
+                    .save(&mut other, &key.to_string())
+                    .unwrap();
+            }
+            MAP.clear(&mut storage).unwrap();
+        }
+
+        // Column families are reopened along with the database
+        let storage = RocksDbStorage::open(dir.path()).unwrap();
+        let other = storage.column_family("other").unwrap();
+        assert_eq!(MAP.at(1).unwrap().may_load(&storage), Ok(None));
+        assert_eq!(
+            MAP.at(1).unwrap().may_load(&other),
+            Ok(Some("1".to_string()))
+        );
+        assert_eq!(
+            MAP.at(2).unwrap().may_load(&other),
+            Ok(Some("2".to_string()))
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::{borrow::Cow, ops::Bound};
+
+use ::sled::{Batch, IVec};
+
+use super::resolve_batch;
+use crate::{
+    storage::{encode_bound, is_empty_range},
+    BackendError, Directed, Encodable, IterableStorage, KeyEncoding, KeyResult, KvResult, Order,
+    RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+type SledEntry = ::sled::Result<(IVec, IVec)>;
+
+fn owned_kv<'a>(entry: SledEntry) -> KvResult<'a> {
+    let (key, value) = entry.map_err(BackendError::new)?;
+    Ok((Cow::Owned(key.to_vec()), Cow::Owned(value.to_vec())))
+}
+
+fn owned_k<'a>(entry: SledEntry) -> KeyResult<'a> {
+    let (key, _) = entry.map_err(BackendError::new)?;
+    Ok(Cow::Owned(key.to_vec()))
+}
+
+/// Implements the storage traits for a sled `Tree`, or a `Db` through its
+/// default tree.
+///
+/// Writes take effect immediately, and batches are applied with
+/// `Tree::apply_batch`. sled has no native range delete, so range deletes scan
+/// the keys in range first, and are only atomic with respect to the rest of
+/// their batch.
+macro_rules! impl_sled_storage {
+    ($tree:ty) => {
+        impl Storage for $tree {
+            fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+                let value = ::sled::Tree::get(self, key).map_err(BackendError::new)?;
+                Ok(value.map(|value| Cow::Owned(value.to_vec())))
+            }
+        }
+
+        impl StorageMut for $tree {
+            fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+                ::sled::Tree::insert(self, key, value).map_err(BackendError::new)?;
+                Ok(())
+            }
+
+            fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+                ::sled::Tree::remove(self, key).map_err(BackendError::new)?;
+                Ok(())
+            }
+
+            fn delete_range_raw(
+                &mut self,
+                low: Bound<Vec<u8>>,
+                high: Bound<Vec<u8>>,
+            ) -> Result<(), BackendError> {
+                let mut batch = WriteBatch::new();
+                batch.delete_range(low, high);
+                self.write_batch(batch)
+            }
+
+            fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+                let writes = resolve_batch(batch, |low, high| {
+                    ::sled::Tree::range::<&[u8], _>(
+                        self,
+                        (
+                            low.as_ref().map(Vec::as_slice),
+                            high.as_ref().map(Vec::as_slice),
+                        ),
+                    )
+                    .keys()
+                    .map(|key| key.map(|key| key.to_vec()).map_err(BackendError::new))
+                    .collect()
+                })?;
+
+                let mut sled_batch = Batch::default();
+                for (key, value) in writes {
+                    match value {
+                        Some(value) => sled_batch.insert(key, value),
+                        None => sled_batch.remove(key),
+                    }
+                }
+                ::sled::Tree::apply_batch(self, sled_batch).map_err(BackendError::new)
+            }
+        }
+
+        impl IterableStorage for $tree {
+            type Keys<'a> = Directed<std::iter::Map<::sled::Iter, fn(SledEntry) -> KeyResult<'a>>>;
+            type Iter<'a> = Directed<std::iter::Map<::sled::Iter, fn(SledEntry) -> KvResult<'a>>>;
+
+            fn keys<K: Encodable<KeyEncoding>>(
+                &self,
+                low: Bound<K>,
+                high: Bound<K>,
+                order: Order,
+            ) -> Result<Self::Keys<'_>, RawStorageError> {
+                let (low, high) = (encode_bound!(low), encode_bound!(high));
+                if is_empty_range(&low, &high) {
+                    return Ok(Directed::empty(order));
+                }
+                let iter = ::sled::Tree::range(self, (low, high)).map(owned_k as fn(_) -> _);
+                Ok(Directed::new(iter, order))
+            }
+
+            fn iter<K: Encodable<KeyEncoding>>(
+                &self,
+                low: Bound<K>,
+                high: Bound<K>,
+                order: Order,
+            ) -> Result<Self::Iter<'_>, RawStorageError> {
+                let (low, high) = (encode_bound!(low), encode_bound!(high));
+                if is_empty_range(&low, &high) {
+                    return Ok(Directed::empty(order));
+                }
+                let iter = ::sled::Tree::range(self, (low, high)).map(owned_kv as fn(_) -> _);
+                Ok(Directed::new(iter, order))
+            }
+        }
+    };
+}
+
+impl_sled_storage!(::sled::Tree);
+impl_sled_storage!(::sled::Db);
+
+#[cfg(test)]
+mod test {
+    use crate::{mock::DisplayEncoding, Order, PriorityQueue};
+
+    fn temporary() -> ::sled::Db {
+        ::sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    crate::storage_conformance_tests!(temporary);
+
+    mod tree {
+        crate::storage_conformance_tests!(|| super::temporary().open_tree("tree").unwrap());
+    }
+
+    #[test]
+    fn test_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let pq: PriorityQueue<u8, String, DisplayEncoding> = PriorityQueue::new(b"pq");
+        {
+            let mut db = ::sled::open(dir.path()).unwrap();
+            pq.push(&mut db, 2, &"two".to_string()).unwrap();
+            pq.push(&mut db, 1, &"one".to_string()).unwrap();
+            db.flush().unwrap();
+        }
+
+        let mut db = ::sled::open(dir.path()).unwrap();
+        assert_eq!(
+            pq.pop(&mut db, Order::Ascending).unwrap(),
+            Some((1, "one".to_string()))
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::{borrow::Cow, ops::Bound, path::Path};
+
+use rusqlite::{params_from_iter, types::Value, Connection, OptionalExtension};
+
+use crate::{
+    storage::{encode_bound, key_of, Page, PagedScan},
+    BackendError, BatchOp, Encodable, IterableStorage, KeyEncoding, KeyResult, KvResult, Order,
+    RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+/// Number of rows fetched per query while iterating.
+const PAGE_SIZE: usize = 256;
+
+/// A storage backend over a single `kv` table of an SQLite database.
+///
+/// Keys are `BLOB`s, which SQLite compares bytewise, so range scans are served
+/// in key order by the primary key index. Iterators fetch [`PAGE_SIZE`] rows
+/// per query, resuming after the last key seen, so they hold no statement open
+/// between pages, and observe writes made through other connections between
+/// pages. [`StorageMut::write_batch`] applies a batch in one SQLite transaction.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    /// Opens or creates the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        Self::from_connection(Connection::open(path).map_err(BackendError::new)?)
+    }
+
+    /// Creates a database that lives in memory, and is dropped with it.
+    pub fn open_in_memory() -> Result<Self, BackendError> {
+        Self::from_connection(Connection::open_in_memory().map_err(BackendError::new)?)
+    }
+
+    /// Uses the `kv` table of an open connection, creating it if missing.
+    pub fn from_connection(conn: Connection) -> Result<Self, BackendError> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS kv (key BLOB PRIMARY KEY, value BLOB NOT NULL) \
+             WITHOUT ROWID",
+            (),
+        )
+        .map_err(BackendError::new)?;
+        Ok(Self { conn })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn into_inner(self) -> Connection {
+        self.conn
+    }
+}
+
+/// Appends the SQL condition for a range to `sql`, and its parameters to `params`.
+fn push_range(
+    sql: &mut String,
+    params: &mut Vec<Value>,
+    low: &Bound<Vec<u8>>,
+    high: &Bound<Vec<u8>>,
+) {
+    let mut conditions = Vec::new();
+    for (bound, included, excluded) in [(low, "key >= ?", "key > ?"), (high, "key <= ?", "key < ?")]
+    {
+        let (condition, key) = match bound {
+            Bound::Included(key) => (included, key),
+            Bound::Excluded(key) => (excluded, key),
+            Bound::Unbounded => continue,
+        };
+        conditions.push(condition);
+        params.push(Value::Blob(key.clone()));
+    }
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+}
+
+fn delete_range(
+    conn: &Connection,
+    low: &Bound<Vec<u8>>,
+    high: &Bound<Vec<u8>>,
+) -> rusqlite::Result<()> {
+    let mut sql = String::from("DELETE FROM kv");
+    let mut params = Vec::new();
+    push_range(&mut sql, &mut params, low, high);
+    conn.prepare_cached(&sql)?
+        .execute(params_from_iter(params))?;
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        let value = self
+            .conn
+            .prepare_cached("SELECT value FROM kv WHERE key = ?")
+            .and_then(|mut stmt| stmt.query_row([key], |row| row.get(0)).optional())
+            .map_err(BackendError::new)?;
+        Ok(value.map(Cow::Owned))
+    }
+}
+
+impl StorageMut for SqliteStorage {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.conn
+            .prepare_cached("INSERT OR REPLACE INTO kv (key, value) VALUES (?, ?)")
+            .and_then(|mut stmt| stmt.execute((key, value)))
+            .map_err(BackendError::new)?;
+        Ok(())
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.conn
+            .prepare_cached("DELETE FROM kv WHERE key = ?")
+            .and_then(|mut stmt| stmt.execute([key]))
+            .map_err(BackendError::new)?;
+        Ok(())
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        delete_range(&self.conn, &low, &high).map_err(BackendError::new)
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        let apply = |conn: &mut Connection| -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            for op in batch {
+                match op {
+                    BatchOp::Put(key, value) => {
+                        tx.prepare_cached("INSERT OR REPLACE INTO kv (key, value) VALUES (?, ?)")?
+                            .execute((key, value))?;
+                    }
+                    BatchOp::Delete(key) => {
+                        tx.prepare_cached("DELETE FROM kv WHERE key = ?")?
+                            .execute([key])?;
+                    }
+                    BatchOp::DeleteRange(low, high) => delete_range(&tx, &low, &high)?,
+                }
+            }
+            tx.commit()
+        };
+        apply(&mut self.conn).map_err(BackendError::new)
+    }
+}
+
+/// Iterator over a range of a [`SqliteStorage`], fetching one page of rows at a
+/// time.
+pub struct SqliteIter<'a> {
+    conn: &'a Connection,
+    with_values: bool,
+    scan: PagedScan,
+}
+
+impl<'a> SqliteIter<'a> {
+    fn new(
+        conn: &'a Connection,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+        order: Order,
+        with_values: bool,
+    ) -> Self {
+        Self {
+            conn,
+            with_values,
+            scan: PagedScan::new(low, high, order),
+        }
+    }
+}
+
+/// Fetches the first [`PAGE_SIZE`] rows between `low` and `high`, in `order`.
+fn fetch(
+    conn: &Connection,
+    low: &Bound<Vec<u8>>,
+    high: &Bound<Vec<u8>>,
+    order: Order,
+    with_values: bool,
+) -> rusqlite::Result<(Page, bool)> {
+    let mut sql = String::from(match with_values {
+        true => "SELECT key, value FROM kv",
+        false => "SELECT key, x'' FROM kv",
+    });
+    let mut params = Vec::new();
+    push_range(&mut sql, &mut params, low, high);
+    sql.push_str(match order {
+        Order::Ascending => " ORDER BY key ASC LIMIT ?",
+        Order::Descending => " ORDER BY key DESC LIMIT ?",
+    });
+    params.push(Value::Integer(PAGE_SIZE as i64));
+
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let rows = stmt.query_map(params_from_iter(params), |row| {
+        Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+    })?;
+    let page = rows.collect::<rusqlite::Result<Page>>()?;
+    let last = page.len() < PAGE_SIZE;
+    Ok((page, last))
+}
+
+impl<'a> Iterator for SqliteIter<'a> {
+    type Item = KvResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (conn, with_values) = (self.conn, self.with_values);
+        self.scan.next(|low, high, order| {
+            fetch(conn, low, high, order, with_values).map_err(BackendError::new)
+        })
+    }
+}
+
+impl IterableStorage for SqliteStorage {
+    type Keys<'a> = std::iter::Map<SqliteIter<'a>, fn(KvResult<'a>) -> KeyResult<'a>>;
+    type Iter<'a> = SqliteIter<'a>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        let iter = SqliteIter::new(
+            &self.conn,
+            encode_bound!(low),
+            encode_bound!(high),
+            order,
+            false,
+        );
+        Ok(iter.map(key_of as fn(_) -> _))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        let iter = SqliteIter::new(
+            &self.conn,
+            encode_bound!(low),
+            encode_bound!(high),
+            order,
+            true,
+        );
+        Ok(iter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{mock::DisplayEncoding, Item, KeyType, Vector};
+
+    use super::*;
+
+    crate::storage_conformance_tests!(|| SqliteStorage::open_in_memory().unwrap());
+
+    #[test]
+    fn test_iter_across_pages() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        let count = PAGE_SIZE as u32 * 2 + 1;
+        let mut batch = WriteBatch::new();
+        for i in 0..count {
+            batch.put(i.to_be_bytes().to_vec(), vec![]);
+        }
+        storage.write_batch(batch).unwrap();
+
+        for order in [Order::Ascending, Order::Descending] {
+            let (low, high) = (Bound::<KeyType<()>>::Unbounded, Bound::Unbounded);
+            let keys: Vec<_> = storage
+                .keys(low, high, order)
+                .unwrap()
+                .map(|key| u32::from_be_bytes(key.unwrap().as_ref().try_into().unwrap()))
+                .collect();
+            let mut expected: Vec<_> = (0..count).collect();
+            if order == Order::Descending {
+                expected.reverse();
+            }
+            assert_eq!(keys, expected);
+        }
+    }
+
+    #[test]
+    fn test_file_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("libkv.db");
+        const VECTOR: Vector<Item<String, DisplayEncoding>> = Vector::new(b"vec");
+        const ITEM: Item<u32, DisplayEncoding> = Item::new(b"item");
+
+        {
+            let mut storage = SqliteStorage::open(&path).unwrap();
+            VECTOR.push(&mut storage, &"a".to_string()).unwrap();
+            VECTOR.push(&mut storage, &"b".to_string()).unwrap();
+            ITEM.save(&mut storage, &7).unwrap();
+        }
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        let values: Vec<_> = VECTOR
+            .iter(&storage, Order::Descending)
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(values, vec!["b", "a"]);
+        assert_eq!(ITEM.may_load(&storage), Ok(Some(7)));
+    }
+}
@@ -0,0 +1,768 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufWriter, Write},
+    ops::{Bound, Range},
+    path::Path,
+};
+
+use memmap2::Mmap;
+
+use super::{
+    crc32,
+    wire::{put_bytes, Decoder},
+};
+use crate::{
+    storage::{encode_bound, is_empty_range, key_of},
+    BackendError, Encodable, IterableStorage, KeyEncoding, KeyResult, KvResult, Order,
+    RawStorageError, Storage,
+};
+
+/// Ends every table, after its footer fields.
+const MAGIC: &[u8; 8] = b"libkvsst";
+/// The index offset, index length and entry count as little-endian `u64`s, the
+/// index's CRC-32 as a little-endian `u32`, then [`MAGIC`].
+const FOOTER_LEN: usize = 8 * 3 + 4 + MAGIC.len();
+
+/// The uncompressed size at which the writer ends a block, by default.
+const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+const RAW: u8 = 0;
+const LZ4: u8 = 1;
+
+/// The most an LZ4 block can expand by when decompressed: each byte of a length
+/// extension adds at most 255 bytes of output.
+const LZ4_MAX_RATIO: u64 = 255;
+
+/// How an [`SsTableWriter`] compresses data blocks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// LZ4 block compression. Blocks that do not shrink are stored raw.
+    Lz4,
+}
+
+fn corrupt(what: impl std::fmt::Display) -> BackendError {
+    BackendError::new(format!("corrupt sstable: {what}"))
+}
+
+/// Where a data block is stored, and the last key in it.
+#[derive(Debug, Clone)]
+struct BlockHandle {
+    last_key: Vec<u8>,
+    offset: u64,
+    stored_len: u64,
+    raw_len: u64,
+    crc: u32,
+    compression: u8,
+}
+
+impl BlockHandle {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), BackendError> {
+        put_bytes(buf, &self.last_key)?;
+        buf.extend_from_slice(&self.offset.to_le_bytes());
+        buf.extend_from_slice(&self.stored_len.to_le_bytes());
+        buf.extend_from_slice(&self.raw_len.to_le_bytes());
+        buf.extend_from_slice(&self.crc.to_le_bytes());
+        buf.push(self.compression);
+        Ok(())
+    }
+
+    fn decode(decoder: &mut Decoder<'_>) -> Option<Self> {
+        Some(Self {
+            last_key: decoder.bytes()?,
+            offset: decoder.u64()?,
+            stored_len: decoder.u64()?,
+            raw_len: decoder.u64()?,
+            crc: decoder.u32()?,
+            compression: decoder.u8()?,
+        })
+    }
+}
+
+/// Writes a sorted table, one entry at a time, to any [`Write`].
+///
+/// Entries are added in strictly ascending key order, and grouped into data
+/// blocks of about [`SsTableWriter::set_block_size`] bytes, each optionally
+/// compressed and checksummed. [`SsTableWriter::finish`] then writes an index
+/// of the blocks, and a footer locating it.
+pub struct SsTableWriter<W: Write> {
+    out: W,
+    offset: u64,
+    block_size: usize,
+    compression: Compression,
+    block: Vec<u8>,
+    last_key: Option<Vec<u8>>,
+    index: Vec<BlockHandle>,
+    entries: u64,
+}
+
+impl SsTableWriter<BufWriter<File>> {
+    /// Creates the file at `path` to write a table to, replacing any file
+    /// already there.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        let file = File::create(path).map_err(BackendError::new)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> SsTableWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            block_size: DEFAULT_BLOCK_SIZE,
+            compression: Compression::None,
+            block: Vec::new(),
+            last_key: None,
+            index: Vec::new(),
+            entries: 0,
+        }
+    }
+
+    /// The uncompressed size at which to end a block. 4 KiB by default.
+    /// Smaller blocks make point reads cheaper, and the index larger.
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.block_size = block_size;
+    }
+
+    /// How to compress blocks. Uncompressed by default, in which case reads
+    /// borrow keys and values straight from the table's bytes.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Adds an entry, whose key must be greater than every key added before.
+    pub fn add(&mut self, key: &[u8], value: &[u8]) -> Result<(), BackendError> {
+        if self.last_key.as_deref().is_some_and(|last| key <= last) {
+            return Err(BackendError::new(
+                "sstable keys must be added in strictly ascending order",
+            ));
+        }
+        if key.len() > u32::MAX as usize || value.len() > u32::MAX as usize {
+            return Err(BackendError::new("sstable entry exceeds 4 GiB"));
+        }
+        put_bytes(&mut self.block, key)?;
+        put_bytes(&mut self.block, value)?;
+        self.last_key = Some(key.to_vec());
+        self.entries += 1;
+        if self.block.len() >= self.block_size {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    /// Adds every entry of `storage`, streaming them in ascending order. The
+    /// keys must all be greater than those added before.
+    pub fn add_all<S: IterableStorage>(&mut self, storage: &S) -> Result<(), BackendError> {
+        for entry in storage.prefix_iter(&[], Order::Ascending)? {
+            let (key, value) = entry?;
+            self.add(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), BackendError> {
+        self.out.write_all(bytes).map_err(BackendError::new)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> Result<(), BackendError> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        // A block is only started by an entry
+        let last_key = self.last_key.clone().unwrap();
+        let raw = std::mem::take(&mut self.block);
+        let compressed = match self.compression {
+            Compression::None => None,
+            Compression::Lz4 => Some(lz4_flex::block::compress(&raw)),
+        };
+        let (stored, compression) = match compressed {
+            Some(compressed) if compressed.len() < raw.len() => (Cow::Owned(compressed), LZ4),
+            _ => (Cow::Borrowed(raw.as_slice()), RAW),
+        };
+        let handle = BlockHandle {
+            last_key,
+            offset: self.offset,
+            stored_len: stored.len() as u64,
+            raw_len: raw.len() as u64,
+            crc: crc32(&stored),
+            compression,
+        };
+        self.write(&stored)?;
+        self.index.push(handle);
+        // Reuse the block's allocation
+        self.block = raw;
+        self.block.clear();
+        Ok(())
+    }
+
+    /// Writes the last block, the index and the footer, and returns the
+    /// flushed writer.
+    pub fn finish(mut self) -> Result<W, BackendError> {
+        self.flush_block()?;
+        let mut index = Vec::new();
+        for handle in &self.index {
+            handle.encode(&mut index)?;
+        }
+        let index_offset = self.offset;
+        self.write(&index)?;
+
+        let mut footer = Vec::with_capacity(FOOTER_LEN);
+        footer.extend_from_slice(&index_offset.to_le_bytes());
+        footer.extend_from_slice(&(index.len() as u64).to_le_bytes());
+        footer.extend_from_slice(&self.entries.to_le_bytes());
+        footer.extend_from_slice(&crc32(&index).to_le_bytes());
+        footer.extend_from_slice(MAGIC);
+        self.write(&footer)?;
+        self.out.flush().map_err(BackendError::new)?;
+        Ok(self.out)
+    }
+}
+
+/// A decoded data block, with the position of each entry's key and value.
+struct Block<'a> {
+    data: Cow<'a, [u8]>,
+    entries: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl<'a> Block<'a> {
+    fn read(table: &'a [u8], handle: &BlockHandle) -> Result<Self, BackendError> {
+        let stored = usize::try_from(handle.offset)
+            .ok()
+            .zip(usize::try_from(handle.stored_len).ok())
+            .and_then(|(offset, len)| table.get(offset..offset.checked_add(len)?))
+            .ok_or_else(|| corrupt("block out of bounds"))?;
+        if crc32(stored) != handle.crc {
+            return Err(corrupt(format_args!(
+                "checksum mismatch in block at offset {}",
+                handle.offset
+            )));
+        }
+        let data = match handle.compression {
+            RAW => Cow::Borrowed(stored),
+            LZ4 => {
+                // Checked before allocating the output, so that a crafted index
+                // cannot claim a huge block
+                let raw_len = Some(handle.raw_len)
+                    .filter(|&len| len <= handle.stored_len.saturating_mul(LZ4_MAX_RATIO))
+                    .and_then(|len| usize::try_from(len).ok())
+                    .ok_or_else(|| {
+                        corrupt(format_args!(
+                            "impossible size for the block at offset {}",
+                            handle.offset
+                        ))
+                    })?;
+                Cow::Owned(lz4_flex::block::decompress(stored, raw_len).map_err(corrupt)?)
+            }
+            tag => return Err(corrupt(format_args!("unknown compression {tag}"))),
+        };
+
+        let mut decoder = Decoder::new(&data);
+        let mut entries = Vec::new();
+        while !decoder.is_empty() {
+            let entry = decoder
+                .bytes_range()
+                .zip(decoder.bytes_range())
+                .ok_or_else(|| corrupt("truncated block entry"))?;
+            entries.push(entry);
+        }
+        Ok(Self { data, entries })
+    }
+
+    fn key(&self, i: usize) -> &[u8] {
+        &self.data[self.entries[i].0.clone()]
+    }
+
+    fn slice(&self, range: Range<usize>) -> Cow<'a, [u8]> {
+        match &self.data {
+            Cow::Borrowed(data) => Cow::Borrowed(&data[range]),
+            Cow::Owned(data) => Cow::Owned(data[range].to_vec()),
+        }
+    }
+
+    fn entry(&self, i: usize) -> (Cow<'a, [u8]>, Cow<'a, [u8]>) {
+        let (key, value) = self.entries[i].clone();
+        (self.slice(key), self.slice(value))
+    }
+
+    /// The first entry at or after `key`.
+    fn seek(&self, key: &[u8]) -> usize {
+        self.entries
+            .partition_point(|(k, _)| &self.data[k.clone()] < key)
+    }
+}
+
+fn below(key: &[u8], low: &Bound<Vec<u8>>) -> bool {
+    match low {
+        Bound::Included(low) => key < low.as_slice(),
+        Bound::Excluded(low) => key <= low.as_slice(),
+        Bound::Unbounded => false,
+    }
+}
+
+fn above(key: &[u8], high: &Bound<Vec<u8>>) -> bool {
+    match high {
+        Bound::Included(high) => key > high.as_slice(),
+        Bound::Excluded(high) => key >= high.as_slice(),
+        Bound::Unbounded => false,
+    }
+}
+
+/// A read-only storage backend over a sorted table, as written by
+/// [`SsTableWriter`].
+///
+/// The table's bytes are held as any `D: AsRef<[u8]>`, such as a `Vec<u8>` or
+/// the memory map returned by [`SsTableStorage::map`]. Opening a table reads
+/// and checks only its index; each read then binary searches the index for the
+/// blocks it needs, and validates their checksums as they are read. Keys and
+/// values of uncompressed blocks are borrowed from the table's bytes.
+pub struct SsTableStorage<D = Mmap> {
+    data: D,
+    index: Vec<BlockHandle>,
+    entries: u64,
+}
+
+impl SsTableStorage<Mmap> {
+    /// Memory-maps the table at `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped, which is
+    /// undefined behavior.
+    pub unsafe fn map(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        let file = File::open(path).map_err(BackendError::new)?;
+        // SAFETY: upheld by the caller
+        let mmap = unsafe { Mmap::map(&file) }.map_err(BackendError::new)?;
+        Self::new(mmap)
+    }
+}
+
+impl SsTableStorage<Vec<u8>> {
+    /// Reads the table at `path` into memory.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, BackendError> {
+        Self::new(std::fs::read(path).map_err(BackendError::new)?)
+    }
+}
+
+impl<D: AsRef<[u8]>> SsTableStorage<D> {
+    /// Opens the table stored in `data`, checking its footer and index.
+    pub fn new(data: D) -> Result<Self, BackendError> {
+        let bytes = data.as_ref();
+        let footer = bytes
+            .len()
+            .checked_sub(FOOTER_LEN)
+            .map(|start| &bytes[start..])
+            .filter(|footer| footer.ends_with(MAGIC))
+            .ok_or_else(|| BackendError::new("not a libkv sstable"))?;
+        let mut decoder = Decoder::new(footer);
+        let (index_offset, index_len, entries, index_crc) = (
+            decoder.u64().unwrap(),
+            decoder.u64().unwrap(),
+            decoder.u64().unwrap(),
+            decoder.u32().unwrap(),
+        );
+
+        let index = usize::try_from(index_offset)
+            .ok()
+            .zip(usize::try_from(index_len).ok())
+            .and_then(|(offset, len)| bytes.get(offset..offset.checked_add(len)?))
+            .ok_or_else(|| corrupt("index out of bounds"))?;
+        if crc32(index) != index_crc {
+            return Err(corrupt("checksum mismatch in index"));
+        }
+        let mut decoder = Decoder::new(index);
+        let mut handles = Vec::new();
+        while !decoder.is_empty() {
+            handles
+                .push(BlockHandle::decode(&mut decoder).ok_or_else(|| corrupt("truncated index"))?);
+        }
+
+        Ok(Self {
+            data,
+            index: handles,
+            entries,
+        })
+    }
+
+    /// The number of entries in the table.
+    pub fn len(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    /// Reads every block, checking its checksum and encoding.
+    pub fn verify(&self) -> Result<(), BackendError> {
+        self.index
+            .iter()
+            .try_for_each(|handle| Block::read(self.data.as_ref(), handle).map(drop))
+    }
+
+    pub fn into_inner(self) -> D {
+        self.data
+    }
+
+    fn range(&self, low: Bound<Vec<u8>>, high: Bound<Vec<u8>>, order: Order) -> SsTableIter<'_> {
+        // Blocks from the first that ends at or after `low`, to the first that
+        // ends after `high`
+        let blocks = match is_empty_range(&low, &high) {
+            true => 0..0,
+            false => {
+                let start = self.index.partition_point(|h| below(&h.last_key, &low));
+                let end = self.index.partition_point(|h| !above(&h.last_key, &high));
+                start..(end + 1).min(self.index.len())
+            }
+        };
+        SsTableIter {
+            table: self.data.as_ref(),
+            index: &self.index,
+            low,
+            high,
+            order,
+            blocks,
+            block: None,
+        }
+    }
+}
+
+impl<D: AsRef<[u8]>> Storage for SsTableStorage<D> {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        let i = self.index.partition_point(|h| h.last_key.as_slice() < key);
+        let Some(handle) = self.index.get(i) else {
+            return Ok(None);
+        };
+        let block = Block::read(self.data.as_ref(), handle)?;
+        let i = block.seek(key);
+        match i < block.entries.len() && block.key(i) == key {
+            true => Ok(Some(block.entry(i).1)),
+            false => Ok(None),
+        }
+    }
+}
+
+/// Iterator over a range of an [`SsTableStorage`], in either [`Order`],
+/// reading one block at a time.
+pub struct SsTableIter<'a> {
+    table: &'a [u8],
+    index: &'a [BlockHandle],
+    low: Bound<Vec<u8>>,
+    high: Bound<Vec<u8>>,
+    order: Order,
+    /// The blocks in range that have not been read yet.
+    blocks: Range<usize>,
+    /// The block being read, and its entries in range that are left.
+    block: Option<(Block<'a>, Range<usize>)>,
+}
+
+impl<'a> Iterator for SsTableIter<'a> {
+    type Item = KvResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((block, entries)) = &mut self.block {
+                let i = match self.order {
+                    Order::Ascending => entries.next(),
+                    Order::Descending => entries.next_back(),
+                };
+                if let Some(i) = i {
+                    return Some(Ok(block.entry(i)));
+                }
+            }
+
+            let i = match self.order {
+                Order::Ascending => self.blocks.next(),
+                Order::Descending => self.blocks.next_back(),
+            }?;
+            let block = match Block::read(self.table, &self.index[i]) {
+                Ok(block) => block,
+                Err(e) => {
+                    self.blocks = 0..0;
+                    self.block = None;
+                    return Some(Err(e));
+                }
+            };
+            let start = block
+                .entries
+                .partition_point(|(key, _)| below(&block.data[key.clone()], &self.low));
+            let end = block
+                .entries
+                .partition_point(|(key, _)| !above(&block.data[key.clone()], &self.high));
+            self.block = Some((block, start..end.max(start)));
+        }
+    }
+}
+
+impl<D: AsRef<[u8]>> IterableStorage for SsTableStorage<D> {
+    type Keys<'a>
+        = std::iter::Map<SsTableIter<'a>, fn(KvResult<'a>) -> KeyResult<'a>>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = SsTableIter<'a>
+    where
+        Self: 'a;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        let iter = self.iter(low, high, order)?;
+        Ok(iter.map(key_of as fn(_) -> _))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        Ok(self.range(encode_bound!(low), encode_bound!(high), order))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::{mock::DisplayEncoding, Item, KeyType, Map, StorageMut, Vector};
+
+    use super::*;
+
+    type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+    /// Keys exercising the edges of the byte ordering, between and around which
+    /// ranges are checked.
+    const EDGES: &[&[u8]] = &[
+        b"",
+        b"\x00",
+        b"\x00\x00",
+        b"a",
+        b"a\x00",
+        b"ab",
+        b"b",
+        b"m",
+        b"vec",
+        b"\xff",
+        b"\xff\xff",
+    ];
+
+    fn entries() -> Entries {
+        const MAP: Map<u32, Item<String, DisplayEncoding>> = Map::new(b"map");
+        const VECTOR: Vector<Item<u32, DisplayEncoding>> = Vector::new(b"vec");
+
+        let mut storage = Entries::new();
+        for i in 0..200 {
+            MAP.at(i)
+                .unwrap()
+                .save(&mut storage, &"value".repeat(i as usize % 7))
+                .unwrap();
+            VECTOR.push(&mut storage, &i).unwrap();
+        }
+        for key in EDGES {
+            storage.set_raw(key.to_vec(), key.repeat(3)).unwrap();
+        }
+        storage
+    }
+
+    fn write(entries: &Entries, block_size: usize, compression: Compression) -> Vec<u8> {
+        let mut writer = SsTableWriter::new(Vec::new());
+        writer.set_block_size(block_size);
+        writer.set_compression(compression);
+        writer.add_all(entries).unwrap();
+        writer.finish().unwrap()
+    }
+
+    fn collect<S: IterableStorage>(
+        storage: &S,
+        low: Bound<&[u8]>,
+        high: Bound<&[u8]>,
+        order: Order,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let raw = |key: &[u8]| KeyType::<()>::Raw(key.to_vec());
+        let (low, high) = (low.map(raw), high.map(raw));
+        storage
+            .iter(low, high, order)
+            .unwrap()
+            .map(|entry| {
+                let (key, value) = entry.unwrap();
+                (key.into_owned(), value.into_owned())
+            })
+            .collect()
+    }
+
+    fn bounds() -> Vec<Bound<&'static [u8]>> {
+        let mut bounds = vec![Bound::Unbounded];
+        for &key in EDGES {
+            bounds.push(Bound::Included(key));
+            bounds.push(Bound::Excluded(key));
+        }
+        bounds
+    }
+
+    #[test]
+    fn test_matches_source() {
+        let entries = entries();
+        for block_size in [1, 100, DEFAULT_BLOCK_SIZE, usize::MAX] {
+            for compression in [Compression::None, Compression::Lz4] {
+                let table = SsTableStorage::new(write(&entries, block_size, compression)).unwrap();
+                table.verify().unwrap();
+                assert_eq!(table.len(), entries.len() as u64);
+
+                for (key, value) in &entries {
+                    assert_eq!(
+                        table.get_raw(key).unwrap().as_deref(),
+                        Some(value.as_slice())
+                    );
+                }
+                for missing in [b"map\xff".as_slice(), b"\x00\x01", b"zzz"] {
+                    assert_eq!(table.get_raw(missing), Ok(None));
+                }
+
+                for low in bounds() {
+                    for high in bounds() {
+                        for order in [Order::Ascending, Order::Descending] {
+                            assert_eq!(
+                                collect(&table, low, high, order),
+                                collect(&entries, low, high, order),
+                                "{low:?}..{high:?} {order:?}, {block_size} byte blocks, \
+                                 {compression:?}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_structures() {
+        const VECTOR: Vector<Item<u32, DisplayEncoding>> = Vector::new(b"vec");
+        let mut writer = SsTableWriter::new(Vec::new());
+        writer.set_block_size(64);
+        writer.add_all(&entries()).unwrap();
+        let table = SsTableStorage::new(writer.finish().unwrap()).unwrap();
+
+        assert_eq!(VECTOR.len(&table), Ok(200));
+        assert_eq!(VECTOR.get(&table, 150), Ok(Some(150)));
+        let values: Vec<_> = VECTOR
+            .iter(&table, Order::Descending)
+            .unwrap()
+            .take(3)
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(values, vec![199, 198, 197]);
+    }
+
+    #[test]
+    fn test_empty() {
+        let table = SsTableStorage::new(write(&Entries::new(), 100, Compression::Lz4)).unwrap();
+        assert!(table.is_empty());
+        assert_eq!(table.get_raw(b""), Ok(None));
+        assert!(collect(&table, Bound::Unbounded, Bound::Unbounded, Order::Ascending).is_empty());
+    }
+
+    #[test]
+    fn test_compression() {
+        let mut entries = Entries::new();
+        for i in 0..100u32 {
+            entries.insert(i.to_be_bytes().to_vec(), vec![7; 100]);
+        }
+        let raw = write(&entries, DEFAULT_BLOCK_SIZE, Compression::None);
+        let compressed = write(&entries, DEFAULT_BLOCK_SIZE, Compression::Lz4);
+        assert!(compressed.len() < raw.len() / 4);
+
+        // Uncompressed values are borrowed from the table
+        let table = SsTableStorage::new(raw).unwrap();
+        let value = table.get_raw(&0u32.to_be_bytes()).unwrap().unwrap();
+        assert!(matches!(value, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_rejects_impossible_block_size() {
+        let mut entries = Entries::new();
+        for i in 0..100u32 {
+            entries.insert(i.to_be_bytes().to_vec(), vec![7; 100]);
+        }
+        let data = write(&entries, DEFAULT_BLOCK_SIZE, Compression::Lz4);
+        let table = SsTableStorage::new(data.clone()).unwrap();
+        let mut handle = table.index[0].clone();
+        assert_eq!(handle.compression, LZ4);
+        assert!(Block::read(&data, &handle).is_ok());
+
+        for raw_len in [handle.stored_len * LZ4_MAX_RATIO + 1, u64::MAX] {
+            handle.raw_len = raw_len;
+            let error = Block::read(&data, &handle).err().unwrap();
+            assert!(error.to_string().contains("impossible size"), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_rejects_unsorted_keys() {
+        let mut writer = SsTableWriter::new(Vec::new());
+        writer.add(b"b", b"").unwrap();
+        assert!(writer.add(b"b", b"").is_err());
+        assert!(writer.add(b"a", b"").is_err());
+        writer.add(b"c", b"").unwrap();
+    }
+
+    #[test]
+    fn test_corruption() {
+        let entries = entries();
+        let data = write(&entries, 100, Compression::None);
+        let table = SsTableStorage::new(data.clone()).unwrap();
+        let (key, _) = entries.iter().nth(50).unwrap();
+        let handle = &table.index[table.index.partition_point(|h| &h.last_key < key)];
+
+        // A corrupted block fails the reads that touch it, and only those
+        let mut corrupted = data.clone();
+        corrupted[handle.offset as usize] ^= 0xFF;
+        let table = SsTableStorage::new(corrupted).unwrap();
+        assert!(table.get_raw(key).is_err());
+        assert!(table.get_raw(entries.keys().next().unwrap()).is_ok());
+        assert!(table.verify().is_err());
+        let mut iter = table
+            .iter(
+                Bound::<KeyType<()>>::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap();
+        assert!(iter.any(|entry| entry.is_err()));
+        assert!(iter.next().is_none());
+
+        // A corrupted index or footer fails the open
+        let mut corrupted = data.clone();
+        let last_index_byte = data.len() - FOOTER_LEN - 1;
+        corrupted[last_index_byte] ^= 0xFF;
+        assert!(SsTableStorage::new(corrupted).is_err());
+        assert!(SsTableStorage::new(&data[..data.len() - 1]).is_err());
+        assert!(SsTableStorage::new(&data[1..]).is_err());
+    }
+
+    #[test]
+    fn test_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("table.sst");
+        let entries = entries();
+        let mut writer = SsTableWriter::create(&path).unwrap();
+        writer.set_compression(Compression::Lz4);
+        writer.add_all(&entries).unwrap();
+        writer.finish().unwrap();
+
+        // SAFETY: the file is not modified while mapped
+        let mapped = unsafe { SsTableStorage::map(&path) }.unwrap();
+        let read = SsTableStorage::read(&path).unwrap();
+        let all = (Bound::Unbounded, Bound::Unbounded);
+        let expected = collect(&entries, all.0, all.1, Order::Ascending);
+        assert_eq!(collect(&mapped, all.0, all.1, Order::Ascending), expected);
+        assert_eq!(collect(&read, all.0, all.1, Order::Ascending), expected);
+    }
+}
@@ -0,0 +1,133 @@
+//! Binary encoding shared by the log and SSTable file formats and the remote
+//! storage protocol. Integers are little-endian, and byte strings are prefixed
+//! by their length as a `u32`.
+
+use std::ops::{Bound, Range};
+
+use crate::{BackendError, BatchOp};
+
+pub(crate) const PUT: u8 = 0;
+pub(crate) const DELETE: u8 = 1;
+pub(crate) const DELETE_RANGE: u8 = 2;
+
+pub(crate) const UNBOUNDED: u8 = 0;
+pub(crate) const INCLUDED: u8 = 1;
+pub(crate) const EXCLUDED: u8 = 2;
+
+/// Appends `bytes` after their length. Fails without writing anything if the
+/// length does not fit in a `u32`. The `put_*` functions may leave `buf` partly
+/// written when they fail, so callers discard it.
+pub(crate) fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) -> Result<(), BackendError> {
+    let len =
+        u32::try_from(bytes.len()).map_err(|_| BackendError::new("byte string exceeds 4 GiB"))?;
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(bytes);
+    Ok(())
+}
+
+pub(crate) fn put_bound(buf: &mut Vec<u8>, bound: &Bound<Vec<u8>>) -> Result<(), BackendError> {
+    match bound {
+        Bound::Included(key) => {
+            buf.push(INCLUDED);
+            put_bytes(buf, key)
+        }
+        Bound::Excluded(key) => {
+            buf.push(EXCLUDED);
+            put_bytes(buf, key)
+        }
+        Bound::Unbounded => {
+            buf.push(UNBOUNDED);
+            Ok(())
+        }
+    }
+}
+
+pub(crate) fn put_op(buf: &mut Vec<u8>, op: &BatchOp) -> Result<(), BackendError> {
+    match op {
+        BatchOp::Put(key, value) => {
+            buf.push(PUT);
+            put_bytes(buf, key)?;
+            put_bytes(buf, value)
+        }
+        BatchOp::Delete(key) => {
+            buf.push(DELETE);
+            put_bytes(buf, key)
+        }
+        BatchOp::DeleteRange(low, high) => {
+            buf.push(DELETE_RANGE);
+            put_bound(buf, low)?;
+            put_bound(buf, high)
+        }
+    }
+}
+
+/// Reads the fields of an encoded buffer, or `None` once it runs out.
+pub(crate) struct Decoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) const fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    /// Whether every byte of the buffer has been read.
+    pub(crate) const fn is_empty(&self) -> bool {
+        self.offset == self.data.len()
+    }
+
+    /// Skips over `len` bytes, returning their position in the buffer.
+    pub(crate) fn take(&mut self, len: usize) -> Option<Range<usize>> {
+        let end = self.offset.checked_add(len)?;
+        if end > self.data.len() {
+            return None;
+        }
+        let range = self.offset..end;
+        self.offset = end;
+        Some(range)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        Some(self.data[self.take(1)?.start])
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        let range = self.take(4)?;
+        Some(u32::from_le_bytes(self.data[range].try_into().ok()?))
+    }
+
+    #[cfg(feature = "sstable")]
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        let range = self.take(8)?;
+        Some(u64::from_le_bytes(self.data[range].try_into().ok()?))
+    }
+
+    /// Skips over a length-prefixed byte string, returning its position.
+    pub(crate) fn bytes_range(&mut self) -> Option<Range<usize>> {
+        let len = self.u32()?;
+        self.take(len as usize)
+    }
+
+    pub(crate) fn bytes(&mut self) -> Option<Vec<u8>> {
+        Some(self.data[self.bytes_range()?].to_vec())
+    }
+
+    pub(crate) fn bound(&mut self) -> Option<Bound<Vec<u8>>> {
+        match self.u8()? {
+            UNBOUNDED => Some(Bound::Unbounded),
+            INCLUDED => Some(Bound::Included(self.bytes()?)),
+            EXCLUDED => Some(Bound::Excluded(self.bytes()?)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn op(&mut self) -> Option<BatchOp> {
+        match self.u8()? {
+            PUT => Some(BatchOp::Put(self.bytes()?, self.bytes()?)),
+            DELETE => Some(BatchOp::Delete(self.bytes()?)),
+            DELETE_RANGE => Some(BatchOp::DeleteRange(self.bound()?, self.bound()?)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::ops::Bound;
+
+/// A single write in a [`WriteBatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOp {
+    Put(Vec<u8>, Vec<u8>),
+    Delete(Vec<u8>),
+    /// Deletes every key between the two bounds.
+    DeleteRange(Bound<Vec<u8>>, Bound<Vec<u8>>),
+}
+
+/// An ordered list of raw writes, applied together by
+/// [`StorageMut::write_batch`](crate::StorageMut::write_batch).
+///
+/// Backends with native batches apply a `WriteBatch` atomically, so structures
+/// stage multi-key updates into a single batch rather than writing key by key.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    pub const fn new() -> Self {
+        Self { ops: Vec::new() }
+    }
+
+    pub fn put(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        self.ops.push(BatchOp::Put(key, value));
+    }
+
+    pub fn delete(&mut self, key: Vec<u8>) {
+        self.ops.push(BatchOp::Delete(key));
+    }
+
+    pub fn delete_range(&mut self, low: Bound<Vec<u8>>, high: Bound<Vec<u8>>) {
+        self.ops.push(BatchOp::DeleteRange(low, high));
+    }
+
+    /// Appends all writes of `other` after the writes of this batch.
+    pub fn extend(&mut self, other: WriteBatch) {
+        self.ops.extend(other.ops);
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, BatchOp> {
+        self.ops.iter()
+    }
+}
+
+impl IntoIterator for WriteBatch {
+    type Item = BatchOp;
+    type IntoIter = std::vec::IntoIter<BatchOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a WriteBatch {
+    type Item = &'a BatchOp;
+    type IntoIter = std::slice::Iter<'a, BatchOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.iter()
+    }
+}
@@ -0,0 +1,49 @@
+//! Serves a libkv storage over TCP to `libkv::RemoteStorage` clients.
+//!
+//! Entries are kept in memory, unless `--log` names a log file to persist them
+//! in. The server listens on `127.0.0.1:7707` unless `--listen` says otherwise.
+
+use std::{collections::BTreeMap, error::Error, net::TcpListener, process::ExitCode};
+
+use libkv::{LogStorage, RemoteServer};
+
+const USAGE: &str = "usage: libkv-server [--listen ADDR] [--log PATH]";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("libkv-server: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut listen = "127.0.0.1:7707".to_string();
+    let mut log = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => listen = args.next().ok_or(USAGE)?,
+            "--log" => log = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    let listener = TcpListener::bind(&listen)?;
+    eprintln!("libkv-server: listening on {}", listener.local_addr()?);
+    let on_accept_error = |e| eprintln!("libkv-server: failed to accept a connection: {e}");
+    match log {
+        Some(path) => {
+            RemoteServer::new(LogStorage::open(path)?).serve(&listener, on_accept_error)?
+        }
+        None => RemoteServer::new(BTreeMap::<Vec<u8>, Vec<u8>>::new())
+            .serve(&listener, on_accept_error)?,
+    }
+    Ok(())
+}
@@ -1,6 +1,46 @@
-use std::marker::PhantomData;
+use std::{borrow::Cow, marker::PhantomData};
 
-use crate::{Codec, Decodable, Encoding, Iter, KeyEncoding, StorageError};
+#[cfg(feature = "async")]
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+#[cfg(feature = "async")]
+use futures_core::Stream;
+
+use crate::{Codec, Decodable, Encodable, Encoding, KeyEncoding, KvResult, StorageError};
+
+/// The byte-prefix under which a data structure stores its keys.
+///
+/// Top-level structures are named by a `Delimited` namespace, which is encoded
+/// with a length prefix (exactly like the key of [`crate::Item::new`]). No
+/// delimited namespace is a prefix of another, so structures named `b"foo"` and
+/// `b"foob"` can never share keys.
+///
+/// `Raw` namespaces are used verbatim. Nested structures use them, since their
+/// prefix is the (already unambiguous) key in their parent. Top-level structures
+/// can opt into them to read data stored with the original, undelimited layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Namespace<'a> {
+    /// A top-level name, encoded with a length prefix.
+    Delimited(Cow<'a, [u8]>),
+    /// A prefix used verbatim.
+    Raw(Cow<'a, [u8]>),
+}
+
+impl Namespace<'_> {
+    /// Returns the byte-prefix of this namespace.
+    pub fn prefix(&self) -> Cow<'_, [u8]> {
+        match self {
+            Self::Delimited(name) => match Encodable::<KeyEncoding>::encode(name) {
+                Ok(prefix) => Cow::Owned(prefix),
+                Err(e) => match e {},
+            },
+            Self::Raw(prefix) => Cow::Borrowed(prefix.as_ref()),
+        }
+    }
+}
 
 /// Trait representing an arbitrary data structure that can be used in a
 /// Key-Value store.
@@ -10,11 +50,11 @@ use crate::{Codec, Decodable, Encoding, Iter, KeyEncoding, StorageError};
 /// - `Enc`: The encoding used to serialize and deserialize the data structure.
 /// - `Value`: The value type, that can be (de)serialized using the `Enc` encoding.
 /// - `DsType`: A type that indicates whether the data structure is terminal or
-///     non-terminal.
+///   non-terminal.
 ///
 /// The trait also requires the following methods:
 /// - `with_prefix`: A constructor that takes a byte-prefix, and returns a handle
-///    to the data structure with that prefix.
+///   to the data structure with that prefix.
 /// - `should_skip_key`: A method that takes a key and returns whether the key
 ///   should be skipped when iterating over the data structure.
 pub trait DataStructure {
@@ -54,45 +94,137 @@ impl sealed::ContainerType for Terminal {}
 pub struct NonTerminal {}
 impl sealed::ContainerType for NonTerminal {}
 
-pub struct DsIter<'a, D: DataStructure> {
-    _marker: PhantomData<D>,
+/// An entry of a data structure, as yielded by [`DsIter`].
+pub type DsResult<D> = Result<
+    (<D as DataStructure>::Key, <D as DataStructure>::Value),
+    StorageError<<D as DataStructure>::Enc>,
+>;
+
+/// Iterator decoding the entries of a data structure from a storage iterator `I`.
+///
+/// `DsIter` is `DoubleEndedIterator` or `Send` whenever `I` is.
+pub struct DsIter<'a, D: DataStructure, I> {
+    _marker: PhantomData<fn() -> (&'a (), D)>,
     prefix: Vec<u8>,
-    iter: Iter<'a, (Vec<u8>, Vec<u8>)>,
+    iter: I,
 }
 
-impl<'a, D: DataStructure> DsIter<'a, D> {
-    pub const fn new(prefix: Vec<u8>, iter: Iter<'a, (Vec<u8>, Vec<u8>)>) -> Self {
+/// What to do with an entry of the storage iterator.
+enum Step<T> {
+    Yield(T),
+    Skip,
+    /// The entry is outside the data structure's prefix, so iteration ends.
+    End,
+}
+
+impl<'a, D: DataStructure, I: Iterator<Item = KvResult<'a>>> DsIter<'a, D, I> {
+    pub const fn new(prefix: Vec<u8>, iter: I) -> Self {
         Self {
             _marker: PhantomData,
             prefix,
             iter,
         }
     }
+
+    fn decode(&self, entry: KvResult<'a>) -> Step<<Self as Iterator>::Item> {
+        decode_entry::<D>(&self.prefix, entry)
+    }
 }
 
-impl<'a, D: DataStructure> Iterator for DsIter<'a, D> {
-    type Item = Result<(D::Key, D::Value), StorageError<D::Enc>>;
+/// Decodes an entry of the storage scan over the data structure at `prefix`.
+fn decode_entry<D: DataStructure>(prefix: &[u8], entry: KvResult<'_>) -> Step<DsResult<D>> {
+    let (key_bytes, val_bytes) = match entry {
+        Ok(entry) => entry,
+        Err(e) => return Step::Yield(Err(StorageError::Backend(e))),
+    };
+    if !key_bytes.starts_with(prefix) {
+        return Step::End;
+    }
+    let key = <<D as DataStructure>::Key as Decodable<KeyEncoding>>::decode(
+        &mut &key_bytes[prefix.len()..],
+    )
+    .map(|k| (D::should_skip_key(&k), k));
+
+    match key {
+        Ok((true, _)) => Step::Skip,
+        Ok((false, key)) => {
+            let val = <D::Value as Decodable<D::Enc>>::decode(&mut val_bytes.as_ref())
+                .map_err(StorageError::ValueDeserialize);
+            Step::Yield(val.map(|val| (key, val)))
+        }
+        Err(e) => Step::Yield(Err(StorageError::KeyDeserialize(e))),
+    }
+}
+
+impl<'a, D: DataStructure, I: Iterator<Item = KvResult<'a>>> Iterator for DsIter<'a, D, I> {
+    type Item = DsResult<D>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let (mut key_bytes, val_bytes) = self.iter.next()?;
-            if !key_bytes.starts_with(&self.prefix) {
-                return None;
+            let entry = self.iter.next()?;
+            match self.decode(entry) {
+                Step::Yield(item) => return Some(item),
+                Step::Skip => continue,
+                Step::End => return None,
             }
-            let key_bytes = key_bytes.split_off(self.prefix.len());
-            let key = <<D as DataStructure>::Key as Decodable<KeyEncoding>>::decode(
-                &mut key_bytes.as_slice(),
-            )
-            .map(|k| (D::should_skip_key(&k), k));
-
-            match key {
-                Ok((true, _)) => continue,
-                Ok((false, key)) => {
-                    let val = <D::Value as Decodable<D::Enc>>::decode(&mut val_bytes.as_slice())
-                        .map_err(StorageError::ValueDeserialize);
-                    return Some(val.map(|val| (key, val)));
-                }
-                Err(e) => return Some(Err(StorageError::KeyDeserialize(e))),
+        }
+    }
+}
+
+impl<'a, D: DataStructure, I: DoubleEndedIterator<Item = KvResult<'a>>> DoubleEndedIterator
+    for DsIter<'a, D, I>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = self.iter.next_back()?;
+            match self.decode(entry) {
+                Step::Yield(item) => return Some(item),
+                Step::Skip => continue,
+                Step::End => return None,
+            }
+        }
+    }
+}
+
+/// Stream decoding the entries of a data structure from a storage stream `S`,
+/// the async counterpart of [`DsIter`].
+///
+/// `DsStream` is `Send` or `Unpin` whenever `S` is.
+#[cfg(feature = "async")]
+pub struct DsStream<'a, D: DataStructure, S> {
+    _marker: PhantomData<fn() -> (&'a (), D)>,
+    prefix: Vec<u8>,
+    stream: S,
+}
+
+#[cfg(feature = "async")]
+impl<'a, D: DataStructure, S: Stream<Item = KvResult<'a>>> DsStream<'a, D, S> {
+    pub const fn new(prefix: Vec<u8>, stream: S) -> Self {
+        Self {
+            _marker: PhantomData,
+            prefix,
+            stream,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, D: DataStructure, S: Stream<Item = KvResult<'a>>> Stream for DsStream<'a, D, S> {
+    type Item = DsResult<D>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // SAFETY: `stream` is structurally pinned. It is never moved out of
+        // `self`, and `DsStream` is only `Unpin` when `S` is.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+        loop {
+            let Some(entry) = ready!(stream.as_mut().poll_next(cx)) else {
+                return Poll::Ready(None);
+            };
+            match decode_entry::<D>(&this.prefix, entry) {
+                Step::Yield(item) => return Poll::Ready(Some(item)),
+                Step::Skip => continue,
+                Step::End => return Poll::Ready(None),
             }
         }
     }
@@ -9,10 +9,55 @@ pub enum KeyDeserializeError {
     NotEnoughBytes(usize, usize),
     #[error("Invalid key length: expected {0}, got {1}")]
     InvalidLength(usize, usize),
+    #[error("Unterminated key: expected a 0x00 0x00 terminator")]
+    Unterminated,
+    #[error("Invalid escape sequence in key: 0x00 0x{0:02x}")]
+    InvalidEscape(u8),
     #[error("Error decoding UTF8 key: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
 }
 
+/// An error raised by a storage backend itself, such as a failed disk read or a
+/// dropped connection.
+///
+/// The underlying error is boxed, so that any backend can report its own error type.
+/// Two `BackendError`s compare equal if their messages are equal.
+#[derive(thiserror::Error, Debug)]
+#[error(transparent)]
+pub struct BackendError(Box<dyn std::error::Error + Send + Sync + 'static>);
+
+impl BackendError {
+    pub fn new(error: impl Into<Box<dyn std::error::Error + Send + Sync + 'static>>) -> Self {
+        Self(error.into())
+    }
+
+    pub fn get_ref(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self.0.as_ref()
+    }
+
+    pub fn into_inner(self) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self.0
+    }
+}
+
+impl PartialEq for BackendError {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl Eq for BackendError {}
+
+/// Errors from raw storage access: either the key could not be encoded, or the
+/// backend failed.
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum RawStorageError {
+    #[error("Error serializing key: {0}")]
+    KeySerialize(#[from] KeySerializeError),
+    #[error("Storage backend error: {0}")]
+    Backend(#[from] BackendError),
+}
+
 #[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum StorageError<Enc: Encoding> {
     #[error("Error serializing key: {0}")]
@@ -23,4 +68,19 @@ pub enum StorageError<Enc: Encoding> {
     ValueSerialize(Enc::EncodeError),
     #[error("Error deserializing value: {0}")]
     ValueDeserialize(Enc::DecodeError),
+    #[error("Index out of bounds: index {0}, length {1}")]
+    IndexOutOfBounds(usize, usize),
+    #[error("Corrupted storage: no value at index {0}, length {1}")]
+    MissingElement(usize, usize),
+    #[error("Storage backend error: {0}")]
+    Backend(#[from] BackendError),
+}
+
+impl<Enc: Encoding> From<RawStorageError> for StorageError<Enc> {
+    fn from(error: RawStorageError) -> Self {
+        match error {
+            RawStorageError::KeySerialize(e) => Self::KeySerialize(e),
+            RawStorageError::Backend(e) => Self::Backend(e),
+        }
+    }
 }
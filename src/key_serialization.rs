@@ -48,6 +48,8 @@ impl Decodable<KeyEncoding> for () {
     }
 }
 
+/// Length-prefixed encoding, which sorts keys by length first: `"b"` sorts before
+/// `"ab"`. Use [`Lexicographic<String>`] for keys that must sort lexicographically.
 impl Encodable<KeyEncoding> for String {
     fn encode(&self) -> Result<Vec<u8>, KeySerializeError> {
         let length = encode_length(self.len())?;
@@ -90,6 +92,8 @@ impl Decodable<KeyEncoding> for std::borrow::Cow<'_, [u8]> {
     }
 }
 
+/// Length-prefixed encoding, which sorts keys by length first. Use
+/// [`Lexicographic<Vec<u8>>`] for keys that must sort lexicographically.
 impl Encodable<KeyEncoding> for Vec<u8> {
     fn encode(&self) -> Result<Vec<u8>, KeySerializeError> {
         let length = encode_length(self.len())?;
@@ -111,6 +115,100 @@ impl Decodable<KeyEncoding> for Vec<u8> {
     }
 }
 
+/// A byte-string key wrapper whose encoding preserves lexicographic ordering.
+///
+/// The default encodings of [`String`], [`Vec<u8>`] and `Cow<[u8]>` are
+/// length-prefixed, so that keys sort by length first. `Lexicographic` instead
+/// escapes every `0x00` byte as `0x00 0xFF` and appends a `0x00 0x00`
+/// terminator, so that encoded keys sort exactly like the unencoded bytes, and
+/// all keys starting with a given byte string share an encoded prefix.
+///
+/// The two layouts are not compatible: use [`crate::Map::migrate_keys`] to
+/// re-encode the keys of an existing map.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lexicographic<T>(pub T);
+
+impl<T> Lexicographic<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> std::ops::Deref for Lexicographic<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl From<String> for Lexicographic<String> {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Lexicographic<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<Vec<u8>> for Lexicographic<Vec<u8>> {
+    fn from(value: Vec<u8>) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&[u8]> for Lexicographic<Vec<u8>> {
+    fn from(value: &[u8]) -> Self {
+        Self(value.to_vec())
+    }
+}
+
+impl<'a> From<std::borrow::Cow<'a, [u8]>> for Lexicographic<std::borrow::Cow<'a, [u8]>> {
+    fn from(value: std::borrow::Cow<'a, [u8]>) -> Self {
+        Self(value)
+    }
+}
+
+impl Encodable<KeyEncoding> for Lexicographic<String> {
+    fn encode(&self) -> Result<Vec<u8>, KeySerializeError> {
+        Ok(encode_escaped(self.0.as_bytes()))
+    }
+}
+
+impl Decodable<KeyEncoding> for Lexicographic<String> {
+    fn decode(bytes: &mut &[u8]) -> Result<Self, KeyDeserializeError> {
+        let data = decode_escaped(bytes)?;
+        String::from_utf8(data).map(Self).map_err(Into::into)
+    }
+}
+
+impl Encodable<KeyEncoding> for Lexicographic<Vec<u8>> {
+    fn encode(&self) -> Result<Vec<u8>, KeySerializeError> {
+        Ok(encode_escaped(&self.0))
+    }
+}
+
+impl Decodable<KeyEncoding> for Lexicographic<Vec<u8>> {
+    fn decode(bytes: &mut &[u8]) -> Result<Self, KeyDeserializeError> {
+        decode_escaped(bytes).map(Self)
+    }
+}
+
+impl Encodable<KeyEncoding> for Lexicographic<std::borrow::Cow<'_, [u8]>> {
+    fn encode(&self) -> Result<Vec<u8>, KeySerializeError> {
+        Ok(encode_escaped(&self.0))
+    }
+}
+
+impl Decodable<KeyEncoding> for Lexicographic<std::borrow::Cow<'_, [u8]>> {
+    fn decode(bytes: &mut &[u8]) -> Result<Self, KeyDeserializeError> {
+        decode_escaped(bytes).map(|data| Self(std::borrow::Cow::Owned(data)))
+    }
+}
+
 impl<K: Encodable<KeyEncoding>> Encodable<KeyEncoding> for Option<K> {
     fn encode(&self) -> Result<Vec<u8>, KeySerializeError> {
         match self {
@@ -224,6 +322,50 @@ macro_rules! impl_sint_keyserde {
 }
 impl_sint_keyserde!(i8, i16, i32, i64, i128, isize);
 
+/// Encodes `data` as an order-preserving, self-delimiting byte string:
+///
+/// 0x00 -> 0x00 0xFF
+/// any other byte -> itself
+/// end of string -> 0x00 0x00
+///
+/// Since the terminator sorts before every escaped or literal byte, a string
+/// sorts before all strings it is a proper prefix of.
+fn encode_escaped(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len() + 2);
+    for &byte in data {
+        encoded.push(byte);
+        if byte == 0x00 {
+            encoded.push(0xFF);
+        }
+    }
+    encoded.extend([0x00, 0x00]);
+    encoded
+}
+
+/// Decodes a byte string from the format used in [`encode_escaped`].
+fn decode_escaped(bytes: &mut &[u8]) -> Result<Vec<u8>, KeyDeserializeError> {
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    loop {
+        match bytes.get(idx..idx + 2) {
+            Some([0x00, 0x00]) => break,
+            Some([0x00, 0xFF]) => {
+                decoded.push(0x00);
+                idx += 2;
+            }
+            Some([0x00, escape]) => return Err(KeyDeserializeError::InvalidEscape(*escape)),
+            Some([byte, _]) => {
+                decoded.push(*byte);
+                idx += 1;
+            }
+            _ => return Err(KeyDeserializeError::Unterminated),
+        }
+    }
+
+    *bytes = &bytes[idx + 2..];
+    Ok(decoded)
+}
+
 /// Encodes `len` as a compact integer with the format:
 ///
 /// [0-4 bits]: length of compact representation, in bytes (0-8), > 8 is invalid
@@ -283,7 +425,7 @@ mod test {
     use crate::{
         decode, encode,
         key_serialization::{decode_length, encode_length},
-        KeyEncoding,
+        KeyDeserializeError, KeyEncoding, Lexicographic,
     };
 
     #[test]
@@ -368,4 +510,76 @@ mod test {
             decode::<(usize, String, Vec<u8>, u64), KeyEncoding>(encoded.as_slice()).unwrap();
         assert_eq!(key, decoded);
     }
+
+    #[test]
+    fn test_lexicographic_serde() {
+        let test_cases: Vec<&[u8]> = vec![
+            b"",
+            b"a",
+            b"ab",
+            b"\x00",
+            b"\x00\x00",
+            b"a\x00b",
+            b"\xff",
+            b"\x00\xff",
+        ];
+        for data in test_cases {
+            let key = Lexicographic(data.to_vec());
+            let encoded = encode::<KeyEncoding>(&key).unwrap();
+            let decoded = decode::<Lexicographic<Vec<u8>>, KeyEncoding>(&encoded).unwrap();
+            assert_eq!(key, decoded);
+        }
+
+        // Self-delimiting, so keys compose inside tuples
+        let key = (
+            Lexicographic("a\0b".to_string()),
+            42u64,
+            Lexicographic(vec![0u8]),
+        );
+        let encoded = encode::<KeyEncoding>(&key).unwrap();
+        let decoded =
+            decode::<(Lexicographic<String>, u64, Lexicographic<Vec<u8>>), KeyEncoding>(&encoded)
+                .unwrap();
+        assert_eq!(key, decoded);
+
+        assert_eq!(
+            decode::<Lexicographic<Vec<u8>>, KeyEncoding>(b"ab"),
+            Err(KeyDeserializeError::Unterminated)
+        );
+        assert_eq!(
+            decode::<Lexicographic<Vec<u8>>, KeyEncoding>(b"a\x00\x01"),
+            Err(KeyDeserializeError::InvalidEscape(0x01))
+        );
+    }
+
+    #[test]
+    fn test_lexicographic_ordering() {
+        let mut keys: Vec<Vec<u8>> = vec![
+            b"".to_vec(),
+            b"\x00".to_vec(),
+            b"\x00\x00".to_vec(),
+            b"\x00\x01".to_vec(),
+            b"\x01".to_vec(),
+            b"a".to_vec(),
+            b"a\x00".to_vec(),
+            b"a\xff".to_vec(),
+            b"ab".to_vec(),
+            b"b".to_vec(),
+            b"\xff".to_vec(),
+            b"\xff\xff".to_vec(),
+        ];
+        keys.sort();
+        let mut encoded: Vec<_> = keys
+            .iter()
+            .map(|k| encode::<KeyEncoding>(&Lexicographic(k.clone())).unwrap())
+            .collect();
+        let expected = encoded.clone();
+        encoded.sort();
+        assert_eq!(encoded, expected);
+
+        // Tuples sort by their first element first
+        let a = encode::<KeyEncoding>(&(Lexicographic(b"a".to_vec()), 9u8)).unwrap();
+        let ab = encode::<KeyEncoding>(&(Lexicographic(b"ab".to_vec()), 0u8)).unwrap();
+        assert!(a < ab);
+    }
 }
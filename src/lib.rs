@@ -1,19 +1,69 @@
+#[cfg(feature = "async")]
+mod async_storage;
+mod backends;
+mod batch;
 mod container;
 mod error;
 mod key_serialization;
 mod serialization;
+mod sorted_view;
 mod storage;
 mod structures;
+mod transaction;
 
-pub use container::{Container, DataStructure, DsIter, NonTerminal, Terminal};
-pub use error::{KeyDeserializeError, KeySerializeError, StorageError};
-pub use key_serialization::{KeyEncoding, KeyType};
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub use backends::{
+    concurrent::{ConcurrentIter, ConcurrentStorage, ConcurrentUpdate},
+    log::LogStorage,
+};
+pub use batch::{BatchOp, WriteBatch};
+pub use container::{Container, DataStructure, DsIter, DsResult, Namespace, NonTerminal, Terminal};
+pub use error::{
+    BackendError, KeyDeserializeError, KeySerializeError, RawStorageError, StorageError,
+};
+pub use key_serialization::{KeyEncoding, KeyType, Lexicographic};
 pub use serialization::{decode, encode, Codec, Decodable, Encodable, Encoding};
-pub use storage::{Iter, IterableStorage, Order, Storage, StorageMut};
+pub use sorted_view::{SortedView, UnorderedStorage};
+pub use storage::{
+    scan_delete_range, Directed, IterableStorage, KeyResult, KvPair, KvResult, Order,
+    SharedStorageMut, Storage, StorageMut,
+};
 pub use structures::*;
+pub use transaction::{Savepoint, Transaction, TransactionIter};
+
+#[cfg(feature = "async")]
+pub use async_storage::{
+    AsyncIterableStorage, AsyncStorage, AsyncStorageMut, BlockingStorage, BlockingStream,
+};
+#[cfg(feature = "fjall")]
+pub use backends::fjall::FjallStorage;
+#[cfg(feature = "lmdb")]
+pub use backends::lmdb::{LmdbIter, LmdbReadTxn, LmdbStorage, LmdbStorageIter, LmdbWriteTxn};
+#[cfg(feature = "redb")]
+pub use backends::redb::RedbStorage;
+#[cfg(feature = "remote")]
+pub use backends::remote::{RemoteIter, RemoteServer, RemoteStorage};
+#[cfg(feature = "rocksdb")]
+pub use backends::rocksdb::{RocksDbIter, RocksDbStorage};
+#[cfg(feature = "sqlite")]
+pub use backends::sqlite::{SqliteIter, SqliteStorage};
+#[cfg(feature = "sstable")]
+pub use backends::sstable::{Compression, SsTableIter, SsTableStorage, SsTableWriter};
+#[cfg(feature = "async")]
+pub use container::DsStream;
 
+#[cfg(feature = "bincode")]
+pub use serialization::_bincode::BincodeEncoding;
 #[cfg(feature = "borsh")]
 pub use serialization::_borsh::BorshEncoding;
+#[cfg(feature = "cbor")]
+pub use serialization::_cbor::CborEncoding;
+#[cfg(feature = "json")]
+pub use serialization::_json::JsonEncoding;
+#[cfg(feature = "postcard")]
+pub use serialization::_postcard::PostcardEncoding;
 
 #[cfg(test)]
 pub mod mock;
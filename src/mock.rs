@@ -1,6 +1,12 @@
-use std::{convert::Infallible, fmt::Display, str::FromStr};
+use std::{
+    borrow::Cow, collections::BTreeMap, convert::Infallible, fmt::Display, ops::Bound, str::FromStr,
+};
 
 use super::serialization::{Decodable, Encodable, Encoding};
+use crate::{
+    BackendError, IterableStorage, KeyEncoding, KeyResult, KvResult, Order, RawStorageError,
+    Storage, StorageMut,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DisplayEncoding;
@@ -22,3 +28,79 @@ impl<T: Display + FromStr<Err: Into<Box<dyn std::error::Error>>>> Decodable<Disp
         Ok(Self::from_str(s).unwrap_or_else(|_| panic!("Failed to parse {}", s)))
     }
 }
+
+/// A `BTreeMap` backend that fails every operation once `failing` is set.
+/// Iterators created while failing yield their first entry, then an error.
+#[derive(Debug, Default)]
+pub struct FailingStorage {
+    pub inner: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub failing: bool,
+}
+
+impl FailingStorage {
+    fn check(&self) -> Result<(), BackendError> {
+        match self.failing {
+            true => Err(BackendError::new("disk on fire")),
+            false => Ok(()),
+        }
+    }
+}
+
+impl Storage for FailingStorage {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        self.check()?;
+        self.inner.get_raw(key)
+    }
+}
+
+impl StorageMut for FailingStorage {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.check()?;
+        self.inner.set_raw(key, value)
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.check()?;
+        self.inner.delete_raw(key)
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        self.check()?;
+        self.inner.delete_range_raw(low, high)
+    }
+}
+
+impl IterableStorage for FailingStorage {
+    type Keys<'a> = Box<dyn Iterator<Item = KeyResult<'a>> + 'a>;
+    type Iter<'a> = Box<dyn Iterator<Item = KvResult<'a>> + 'a>;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        let iter = self.iter(low, high, order)?;
+        Ok(Box::new(iter.map(|entry| entry.map(|(k, _)| k))))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        let iter = IterableStorage::iter(&self.inner, low, high, order)?;
+        match self.failing {
+            true => {
+                let error = Err(BackendError::new("disk on fire"));
+                Ok(Box::new(iter.take(1).chain(std::iter::once(error))))
+            }
+            false => Ok(Box::new(iter)),
+        }
+    }
+}
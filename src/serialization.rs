@@ -32,33 +32,125 @@ pub fn decode<T: Decodable<E>, E: Encoding>(bytes: &[u8]) -> Result<T, E::Decode
     T::decode(&mut bytes)
 }
 
-// /// Encoding that allows any type implementing `serde::Serialize` and `serde::Deserialize` to be
-// /// used as a codec.
-// #[cfg(feature = "serde")]
-// #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-// pub struct SerdeEncoding<'de, S: serde::Serializer, D: serde::Deserializer<'de>>(
-//     std::marker::PhantomData<(S, &'de D)>,
-// );
-
-// #[cfg(feature = "serde")]
-// impl<'de, S: serde::Serializer, D: serde::Deserializer<'de>> Encoding for SerdeEncoding<'de, S, D> {
-//     type EncodeError = S::Error;
-//     type DecodeError = D::Error;
-// }
-
-// #[cfg(feature = "serde")]
-// impl<'de, T, S: serde::Serializer, D: serde::Deserializer<'de>> Encodable<SerdeEncoding<'de, S, D>>
-//     for T
-// where
-//     T: serde::Serialize,
-// {
-//     fn encode(&self) -> Result<Vec<u8>, S::Error> {
-//         // serde_json::to_string uses 128 bytes as the default capacity
-//         let mut buf = Vec::with_capacity(128);
-//         S::serialize_
-//         S::serialize
-//     }
-// }
+/// Encoding using JSON, for any type implementing `serde::Serialize` and
+/// `serde::de::DeserializeOwned`.
+#[cfg(feature = "json")]
+pub(crate) mod _json {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct JsonEncoding;
+
+    impl Encoding for JsonEncoding {
+        type EncodeError = ::serde_json::Error;
+        type DecodeError = ::serde_json::Error;
+    }
+
+    impl<T: ::serde::Serialize> Encodable<JsonEncoding> for T {
+        fn encode(&self) -> Result<Vec<u8>, ::serde_json::Error> {
+            ::serde_json::to_vec(self)
+        }
+    }
+
+    impl<T: ::serde::de::DeserializeOwned> Decodable<JsonEncoding> for T {
+        fn decode(bytes: &mut &[u8]) -> Result<Self, ::serde_json::Error> {
+            let mut stream = ::serde_json::Deserializer::from_slice(bytes).into_iter();
+            let value = match stream.next() {
+                Some(value) => value?,
+                None => return Err(::serde::de::Error::custom("EOF while parsing a value")),
+            };
+            *bytes = &bytes[stream.byte_offset()..];
+            Ok(value)
+        }
+    }
+}
+
+/// Encoding using CBOR, for any type implementing `serde::Serialize` and
+/// `serde::de::DeserializeOwned`.
+#[cfg(feature = "cbor")]
+pub(crate) mod _cbor {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct CborEncoding;
+
+    impl Encoding for CborEncoding {
+        type EncodeError = ::ciborium::ser::Error<std::io::Error>;
+        type DecodeError = ::ciborium::de::Error<std::io::Error>;
+    }
+
+    impl<T: ::serde::Serialize> Encodable<CborEncoding> for T {
+        fn encode(&self) -> Result<Vec<u8>, ::ciborium::ser::Error<std::io::Error>> {
+            let mut buf = Vec::new();
+            ::ciborium::into_writer(self, &mut buf)?;
+            Ok(buf)
+        }
+    }
+
+    impl<T: ::serde::de::DeserializeOwned> Decodable<CborEncoding> for T {
+        fn decode(bytes: &mut &[u8]) -> Result<Self, ::ciborium::de::Error<std::io::Error>> {
+            // Reading from a byte slice advances it past the consumed bytes.
+            ::ciborium::from_reader(bytes)
+        }
+    }
+}
+
+/// Encoding using bincode, for any type implementing `serde::Serialize` and
+/// `serde::de::DeserializeOwned`.
+#[cfg(feature = "bincode")]
+pub(crate) mod _bincode {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct BincodeEncoding;
+
+    impl Encoding for BincodeEncoding {
+        type EncodeError = ::bincode::Error;
+        type DecodeError = ::bincode::Error;
+    }
+
+    impl<T: ::serde::Serialize> Encodable<BincodeEncoding> for T {
+        fn encode(&self) -> Result<Vec<u8>, ::bincode::Error> {
+            ::bincode::serialize(self)
+        }
+    }
+
+    impl<T: ::serde::de::DeserializeOwned> Decodable<BincodeEncoding> for T {
+        fn decode(bytes: &mut &[u8]) -> Result<Self, ::bincode::Error> {
+            // Reading from a byte slice advances it past the consumed bytes.
+            ::bincode::deserialize_from(bytes)
+        }
+    }
+}
+
+/// Encoding using postcard, for any type implementing `serde::Serialize` and
+/// `serde::de::DeserializeOwned`.
+#[cfg(feature = "postcard")]
+pub(crate) mod _postcard {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PostcardEncoding;
+
+    impl Encoding for PostcardEncoding {
+        type EncodeError = ::postcard::Error;
+        type DecodeError = ::postcard::Error;
+    }
+
+    impl<T: ::serde::Serialize> Encodable<PostcardEncoding> for T {
+        fn encode(&self) -> Result<Vec<u8>, ::postcard::Error> {
+            ::postcard::to_allocvec(self)
+        }
+    }
+
+    impl<T: ::serde::de::DeserializeOwned> Decodable<PostcardEncoding> for T {
+        fn decode(bytes: &mut &[u8]) -> Result<Self, ::postcard::Error> {
+            let (value, rest) = ::postcard::take_from_bytes(bytes)?;
+            *bytes = rest;
+            Ok(value)
+        }
+    }
+}
 
 #[cfg(feature = "borsh")]
 pub(crate) mod _borsh {
@@ -79,8 +171,251 @@ pub(crate) mod _borsh {
     }
 
     impl<T: ::borsh::de::BorshDeserialize> Decodable<BorshEncoding> for T {
-        fn decode(bytes: &[u8]) -> Result<Self, ::borsh::io::Error> {
-            ::borsh::from_slice(bytes)
+        fn decode(bytes: &mut &[u8]) -> Result<Self, ::borsh::io::Error> {
+            // Unlike `borsh::from_slice`, this does not reject trailing bytes, and
+            // advances the slice past the consumed bytes.
+            T::deserialize(bytes)
+        }
+    }
+}
+
+#[cfg(all(
+    test,
+    any(
+        feature = "json",
+        feature = "cbor",
+        feature = "bincode",
+        feature = "postcard"
+    )
+))]
+mod serde_test {
+    use std::{collections::BTreeMap, ops::Bound};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{encode, Codec, Decodable, Encoding, Item, Map, Order, StorageError};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Account {
+        name: String,
+        balance: u64,
+        tags: Vec<String>,
+    }
+
+    fn account(i: u64) -> Account {
+        Account {
+            name: format!("account-{i}"),
+            balance: i * 100,
+            tags: vec!["a".to_string(); i as usize],
+        }
+    }
+
+    fn round_trip<Enc>()
+    where
+        Enc: Encoding + std::fmt::Debug,
+        Account: Codec<Enc>,
+        u64: Codec<Enc>,
+        String: Codec<Enc>,
+    {
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+
+        let item: Item<Account, Enc> = Item::new(b"account");
+        item.save(&mut storage, &account(1)).unwrap();
+        assert_eq!(item.may_load(&storage).unwrap(), Some(account(1)));
+
+        // Decoding must consume exactly the bytes of one value.
+        let bytes = [
+            encode::<Enc>(&account(2)).unwrap(),
+            encode::<Enc>(&42u64).unwrap(),
+        ]
+        .concat();
+        let mut slice = bytes.as_slice();
+        assert_eq!(
+            <Account as Decodable<Enc>>::decode(&mut slice).unwrap(),
+            account(2)
+        );
+        assert_eq!(<u64 as Decodable<Enc>>::decode(&mut slice).unwrap(), 42);
+        assert!(slice.is_empty());
+
+        let map: Map<u64, Item<Account, Enc>> = Map::new(b"map");
+        for i in 0..5 {
+            map.at(i).unwrap().save(&mut storage, &account(i)).unwrap();
+        }
+        let accounts = map
+            .range(
+                &storage,
+                Bound::Included(0),
+                Bound::Excluded(5),
+                Order::Descending,
+            )
+            .unwrap()
+            .map(|res| res.map(|(k, v)| (k.0, v)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            accounts,
+            (0..5).rev().map(|i| (i, account(i))).collect::<Vec<_>>()
+        );
+
+        // Decoding a value of the wrong shape is surfaced as a storage error.
+        let wrong: Item<Account, Enc> = Item::new(b"wrong");
+        let string: Item<String, Enc> = Item::new(b"wrong");
+        string
+            .save(&mut storage, &"not an account".to_string())
+            .unwrap();
+        assert!(matches!(
+            wrong.may_load(&storage),
+            Err(StorageError::ValueDeserialize(_))
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        round_trip::<super::_json::JsonEncoding>();
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor() {
+        round_trip::<super::_cbor::CborEncoding>();
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_bincode() {
+        round_trip::<super::_bincode::BincodeEncoding>();
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn test_postcard() {
+        round_trip::<super::_postcard::PostcardEncoding>();
+    }
+}
+
+#[cfg(all(test, feature = "borsh"))]
+mod borsh_test {
+    use std::{collections::BTreeMap, ops::Bound};
+
+    use borsh::{BorshDeserialize, BorshSerialize};
+
+    use super::_borsh::BorshEncoding;
+    use crate::{encode, Decodable, Item, Map, Order, PriorityQueue, StorageError};
+
+    #[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+    struct Account {
+        name: String,
+        balance: u64,
+        tags: Vec<String>,
+    }
+
+    fn account(i: u64) -> Account {
+        Account {
+            name: format!("account-{i}"),
+            balance: i * 100,
+            tags: vec!["a".to_string(); i as usize],
         }
     }
+
+    #[test]
+    fn test_borsh_decode_advances() {
+        let bytes = [
+            encode::<BorshEncoding>(&account(1)).unwrap(),
+            encode::<BorshEncoding>(&42u64).unwrap(),
+            encode::<BorshEncoding>(&"tail".to_string()).unwrap(),
+        ]
+        .concat();
+
+        let mut slice = bytes.as_slice();
+        let first = <Account as Decodable<BorshEncoding>>::decode(&mut slice).unwrap();
+        assert_eq!(first, account(1));
+        let second = <u64 as Decodable<BorshEncoding>>::decode(&mut slice).unwrap();
+        assert_eq!(second, 42);
+        let third = <String as Decodable<BorshEncoding>>::decode(&mut slice).unwrap();
+        assert_eq!(third, "tail");
+        assert!(slice.is_empty());
+
+        // Not enough bytes is an error rather than a panic
+        let mut slice = &bytes[..4];
+        assert!(<Account as Decodable<BorshEncoding>>::decode(&mut slice).is_err());
+    }
+
+    #[test]
+    fn test_borsh_item() {
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let item: Item<Account, BorshEncoding> = Item::new(b"account");
+        let pair: Item<(Account, u64), BorshEncoding> = Item::new(b"pair");
+
+        assert!(matches!(item.may_load(&storage), Ok(None)));
+        item.save(&mut storage, &account(1)).unwrap();
+        pair.save(&mut storage, &(account(2), 42)).unwrap();
+        assert_eq!(item.may_load(&storage).unwrap(), Some(account(1)));
+        assert_eq!(pair.may_load(&storage).unwrap(), Some((account(2), 42)));
+
+        let truncated: Item<u64, BorshEncoding> = Item::new(b"truncated");
+        let truncated_key =
+            encode::<crate::KeyEncoding>(&std::borrow::Cow::Borrowed(b"truncated".as_slice()))
+                .unwrap();
+        storage.insert(truncated_key, vec![1, 2]);
+        assert!(matches!(
+            truncated.may_load(&storage),
+            Err(StorageError::ValueDeserialize(_))
+        ));
+    }
+
+    #[test]
+    fn test_borsh_map() {
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let map: Map<String, Item<Account, BorshEncoding>> = Map::new(b"accounts");
+
+        for i in 0..5 {
+            let key = format!("k{i}");
+            map.at(key)
+                .unwrap()
+                .save(&mut storage, &account(i))
+                .unwrap();
+        }
+        let accounts = map
+            .range(
+                &storage,
+                Bound::Included("k1".to_string()),
+                Bound::Excluded("k4".to_string()),
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.map(|(k, v)| (k.0, v)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            accounts,
+            (1..4)
+                .map(|i| (format!("k{i}"), account(i)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_borsh_priority_queue() {
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let pq: PriorityQueue<u32, Account, BorshEncoding> = PriorityQueue::new(b"pq");
+
+        pq.push(&mut storage, 3, &account(3)).unwrap();
+        pq.push(&mut storage, 1, &account(1)).unwrap();
+        pq.push(&mut storage, 2, &account(2)).unwrap();
+
+        assert_eq!(
+            pq.pop(&mut storage, Order::Descending).unwrap(),
+            Some((3, account(3)))
+        );
+        assert_eq!(
+            pq.pop(&mut storage, Order::Ascending).unwrap(),
+            Some((1, account(1)))
+        );
+        assert_eq!(
+            pq.pop(&mut storage, Order::Ascending).unwrap(),
+            Some((2, account(2)))
+        );
+        assert_eq!(pq.pop(&mut storage, Order::Ascending).unwrap(), None);
+    }
 }
@@ -0,0 +1,193 @@
+use std::{borrow::Cow, collections::HashMap, ops::Bound};
+
+use crate::{
+    storage::{encode_bound, is_empty_range},
+    BackendError, Encodable, IterableStorage, KeyEncoding, KeyResult, KeySerializeError, KvResult,
+    Order, RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+type Entry<'a> = (&'a [u8], &'a [u8]);
+
+/// An in-memory storage backend that can enumerate all of its entries, in no
+/// particular order.
+pub trait UnorderedStorage: Storage {
+    type Entries<'a>: Iterator<Item = Entry<'a>>
+    where
+        Self: 'a;
+
+    fn entries(&self) -> Self::Entries<'_>;
+}
+
+fn as_slices<'a>((key, value): (&'a Vec<u8>, &'a Vec<u8>)) -> Entry<'a> {
+    (key, value)
+}
+
+impl UnorderedStorage for HashMap<Vec<u8>, Vec<u8>> {
+    type Entries<'a> = std::iter::Map<
+        std::collections::hash_map::Iter<'a, Vec<u8>, Vec<u8>>,
+        fn((&'a Vec<u8>, &'a Vec<u8>)) -> Entry<'a>,
+    >;
+
+    fn entries(&self) -> Self::Entries<'_> {
+        self.iter().map(as_slices as fn(_) -> _)
+    }
+}
+
+/// Adapter implementing [`IterableStorage`] for an unordered backend, such as a
+/// `HashMap`, by sorting on every iteration.
+///
+/// Reads and writes are passed through to the backend unchanged. Each call to
+/// [`IterableStorage::iter`] or [`IterableStorage::keys`] scans every entry of
+/// the backend, and sorts the `m` entries within bounds: O(n + m log m) time and
+/// O(m) memory, however few entries are consumed. Entries are borrowed from the
+/// backend, not copied. This suits tests and small caches, not large stores.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SortedView<S: UnorderedStorage> {
+    inner: S,
+}
+
+impl<S: UnorderedStorage> SortedView<S> {
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Returns the entries within bounds, sorted in `order`.
+    fn sorted<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Vec<Entry<'_>>, KeySerializeError> {
+        let low = encode_bound!(low);
+        let high = encode_bound!(high);
+        if is_empty_range(&low, &high) {
+            return Ok(Vec::new());
+        }
+
+        let range = (
+            low.as_ref().map(Vec::as_slice),
+            high.as_ref().map(Vec::as_slice),
+        );
+        let mut entries: Vec<_> = self
+            .inner
+            .entries()
+            .filter(|(key, _)| std::ops::RangeBounds::<&[u8]>::contains(&range, key))
+            .collect();
+        match order {
+            Order::Ascending => entries.sort_unstable_by(|a, b| a.0.cmp(b.0)),
+            Order::Descending => entries.sort_unstable_by(|a, b| b.0.cmp(a.0)),
+        }
+        Ok(entries)
+    }
+}
+
+impl<S: UnorderedStorage> Storage for SortedView<S> {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        self.inner.get_raw(key)
+    }
+}
+
+impl<S: UnorderedStorage + StorageMut> StorageMut for SortedView<S> {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.inner.set_raw(key, value)
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.inner.delete_raw(key)
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        self.inner.delete_range_raw(low, high)
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        self.inner.write_batch(batch)
+    }
+}
+
+fn borrow_kv<'a>((k, v): Entry<'a>) -> KvResult<'a> {
+    Ok((Cow::Borrowed(k), Cow::Borrowed(v)))
+}
+
+fn borrow_k<'a>((k, _): Entry<'a>) -> KeyResult<'a> {
+    Ok(Cow::Borrowed(k))
+}
+
+type Sorted<'a> = std::vec::IntoIter<Entry<'a>>;
+
+impl<S: UnorderedStorage> IterableStorage for SortedView<S> {
+    type Keys<'a>
+        = std::iter::Map<Sorted<'a>, fn(Entry<'a>) -> KeyResult<'a>>
+    where
+        S: 'a;
+    type Iter<'a>
+        = std::iter::Map<Sorted<'a>, fn(Entry<'a>) -> KvResult<'a>>
+    where
+        S: 'a;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        let entries = self.sorted(low, high, order)?;
+        Ok(entries.into_iter().map(borrow_k as fn(_) -> _))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        let entries = self.sorted(low, high, order)?;
+        Ok(entries.into_iter().map(borrow_kv as fn(_) -> _))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{mock::DisplayEncoding, Order, PriorityQueue};
+
+    use super::SortedView;
+
+    crate::storage_conformance_tests!(|| SortedView::new(HashMap::new()));
+
+    #[test]
+    fn test_sorted_view() {
+        let mut storage = SortedView::new(HashMap::new());
+        let pq: PriorityQueue<i32, String, DisplayEncoding> = PriorityQueue::new(b"pq");
+        for i in [3, -1, 2, 0] {
+            pq.push(&mut storage, i, &i.to_string()).unwrap();
+        }
+
+        assert_eq!(
+            pq.pop(&mut storage, Order::Descending).unwrap(),
+            Some((3, "3".to_string()))
+        );
+        assert_eq!(
+            pq.pop(&mut storage, Order::Ascending).unwrap(),
+            Some((-1, "-1".to_string()))
+        );
+        assert_eq!(storage.into_inner().len(), 2);
+    }
+}
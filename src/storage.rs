@@ -1,103 +1,456 @@
-use crate::{Encodable, KeyEncoding};
-
-use super::KeySerializeError;
-use std::ops::Bound;
+use crate::{BackendError, BatchOp, Encodable, KeyEncoding, KeyType, RawStorageError, WriteBatch};
+#[cfg(any(feature = "sqlite", feature = "lmdb", feature = "remote"))]
+use std::collections::VecDeque;
+use std::{
+    borrow::Cow,
+    ops::{Bound, RangeBounds},
+};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Order {
     Ascending,
     Descending,
 }
 
+macro_rules! encode_bound {
+    ($bound:expr) => {
+        match $bound {
+            Bound::Included(k) => Bound::Included(k.encode()?),
+            Bound::Excluded(k) => Bound::Excluded(k.encode()?),
+            Bound::Unbounded => Bound::Unbounded,
+        }
+    };
+}
+pub(crate) use encode_bound;
+
+/// Storage backends report their own failures as a [`BackendError`]. Infallible
+/// in-memory backends simply always return `Ok`.
+///
+/// Reads return [`Cow`]s, so that in-memory backends can lend out their values
+/// without copying them, while other backends return owned buffers.
 pub trait Storage {
     fn get<K: Encodable<KeyEncoding>>(
         &self,
         key: &K,
-    ) -> Result<Option<Vec<u8>>, KeySerializeError> {
-        Ok(self.get_raw(&key.encode()?))
+    ) -> Result<Option<Cow<'_, [u8]>>, RawStorageError> {
+        Ok(self.get_raw(&key.encode()?)?)
     }
 
-    fn get_raw(&self, key: &[u8]) -> Option<Vec<u8>>;
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError>;
 }
 pub trait StorageMut: Storage {
     fn set<K: Encodable<KeyEncoding>>(
         &mut self,
         key: &K,
         value: Vec<u8>,
-    ) -> Result<(), KeySerializeError> {
-        self.set_raw(key.encode()?, value);
+    ) -> Result<(), RawStorageError> {
+        Ok(self.set_raw(key.encode()?, value)?)
+    }
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError>;
+
+    fn delete<K: Encodable<KeyEncoding>>(&mut self, key: &K) -> Result<(), RawStorageError> {
+        Ok(self.delete_raw(&key.encode()?)?)
+    }
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError>;
+
+    fn delete_range<K: Encodable<KeyEncoding>>(
+        &mut self,
+        low: Bound<K>,
+        high: Bound<K>,
+    ) -> Result<(), RawStorageError> {
+        Ok(self.delete_range_raw(encode_bound!(low), encode_bound!(high))?)
+    }
+    /// Deletes every key between `low` and `high`. Backends without a native
+    /// range delete can implement this with [`scan_delete_range`].
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError>;
+
+    /// Applies the writes of `batch` in order.
+    ///
+    /// Backends with native batches should override this to apply the batch
+    /// atomically. The default applies each write in turn, so a failure may leave
+    /// the batch partially applied.
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        for op in batch {
+            match op {
+                BatchOp::Put(key, value) => self.set_raw(key, value)?,
+                BatchOp::Delete(key) => self.delete_raw(&key)?,
+                BatchOp::DeleteRange(low, high) => self.delete_range_raw(low, high)?,
+            }
+        }
         Ok(())
     }
-    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>);
+}
 
-    fn delete<K: Encodable<KeyEncoding>>(&mut self, key: &K) -> Result<(), KeySerializeError> {
-        self.delete_raw(&key.encode()?);
+/// Storage that can be written through a shared reference, so that threads can
+/// share it without a lock around it.
+///
+/// A shared reference to a `SharedStorageMut` is itself a [`StorageMut`], so
+/// structures write to shared storage through `&mut &storage`.
+pub trait SharedStorageMut: Storage {
+    fn set<K: Encodable<KeyEncoding>>(
+        &self,
+        key: &K,
+        value: Vec<u8>,
+    ) -> Result<(), RawStorageError> {
+        Ok(self.set_raw(key.encode()?, value)?)
+    }
+    fn set_raw(&self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError>;
+
+    fn delete<K: Encodable<KeyEncoding>>(&self, key: &K) -> Result<(), RawStorageError> {
+        Ok(self.delete_raw(&key.encode()?)?)
+    }
+    fn delete_raw(&self, key: &[u8]) -> Result<(), BackendError>;
+
+    fn delete_range<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+    ) -> Result<(), RawStorageError> {
+        Ok(self.delete_range_raw(encode_bound!(low), encode_bound!(high))?)
+    }
+    /// See [`StorageMut::delete_range_raw`].
+    fn delete_range_raw(
+        &self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError>;
+
+    /// See [`StorageMut::write_batch`].
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), BackendError> {
+        for op in batch {
+            match op {
+                BatchOp::Put(key, value) => self.set_raw(key, value)?,
+                BatchOp::Delete(key) => self.delete_raw(&key)?,
+                BatchOp::DeleteRange(low, high) => self.delete_range_raw(low, high)?,
+            }
+        }
         Ok(())
     }
-    fn delete_raw(&mut self, key: &[u8]);
 }
 
-pub type Iter<'a, T> = Box<dyn Iterator<Item = T> + 'a>;
+impl<S: Storage + ?Sized> Storage for &S {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        S::get_raw(self, key)
+    }
+}
+
+impl<S: SharedStorageMut + ?Sized> StorageMut for &S {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        S::set_raw(self, key, value)
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        S::delete_raw(self, key)
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        S::delete_range_raw(self, low, high)
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        S::write_batch(self, batch)
+    }
+}
+
+/// A key-value pair, possibly borrowed from the storage backend.
+pub type KvPair<'a> = (Cow<'a, [u8]>, Cow<'a, [u8]>);
+/// A key yielded by [`IterableStorage::keys`].
+pub type KeyResult<'a> = Result<Cow<'a, [u8]>, BackendError>;
+/// A key-value pair yielded by [`IterableStorage::iter`].
+pub type KvResult<'a> = Result<KvPair<'a>, BackendError>;
 
+/// Backends return their own concrete iterator types, so scans need no allocation
+/// or dynamic dispatch, and callers get `DoubleEndedIterator` or `Send` wherever
+/// the backend's iterators implement them.
+///
+/// Iterators yield a [`BackendError`] if the backend fails mid-scan, after which
+/// they should not be polled further.
 pub trait IterableStorage: Storage {
+    type Keys<'a>: Iterator<Item = KeyResult<'a>>
+    where
+        Self: 'a;
+    type Iter<'a>: Iterator<Item = KvResult<'a>>
+    where
+        Self: 'a;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError>;
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError>;
+
+    /// Iterates over every key starting with `prefix`. The default scans the
+    /// range from `prefix` to its successor; backends with a native prefix scan
+    /// may override it.
+    fn prefix_keys(&self, prefix: &[u8], order: Order) -> Result<Self::Keys<'_>, BackendError> {
+        let (low, high) = prefix_range(prefix);
+        match self.keys(low.map(KeyType::<()>::Raw), high.map(KeyType::Raw), order) {
+            Ok(keys) => Ok(keys),
+            Err(RawStorageError::KeySerialize(e)) => match e {},
+            Err(RawStorageError::Backend(e)) => Err(e),
+        }
+    }
+
+    /// Iterates over every entry whose key starts with `prefix`. See
+    /// [`IterableStorage::prefix_keys`].
+    fn prefix_iter(&self, prefix: &[u8], order: Order) -> Result<Self::Iter<'_>, BackendError> {
+        let (low, high) = prefix_range(prefix);
+        match self.iter(low.map(KeyType::<()>::Raw), high.map(KeyType::Raw), order) {
+            Ok(iter) => Ok(iter),
+            Err(RawStorageError::KeySerialize(e)) => match e {},
+            Err(RawStorageError::Backend(e)) => Err(e),
+        }
+    }
+}
+
+impl<S: IterableStorage + ?Sized> IterableStorage for &S {
+    type Keys<'a>
+        = S::Keys<'a>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = S::Iter<'a>
+    where
+        Self: 'a;
+
     fn keys<K: Encodable<KeyEncoding>>(
         &self,
         low: Bound<K>,
         high: Bound<K>,
         order: Order,
-    ) -> Result<Iter<Vec<u8>>, KeySerializeError>;
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        S::keys(self, low, high, order)
+    }
 
-    #[allow(clippy::type_complexity)]
     fn iter<K: Encodable<KeyEncoding>>(
         &self,
         low: Bound<K>,
         high: Bound<K>,
         order: Order,
-    ) -> Result<Iter<(Vec<u8>, Vec<u8>)>, KeySerializeError>;
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        S::iter(self, low, high, order)
+    }
+
+    fn prefix_keys(&self, prefix: &[u8], order: Order) -> Result<Self::Keys<'_>, BackendError> {
+        S::prefix_keys(self, prefix, order)
+    }
+
+    fn prefix_iter(&self, prefix: &[u8], order: Order) -> Result<Self::Iter<'_>, BackendError> {
+        S::prefix_iter(self, prefix, order)
+    }
+}
+
+/// Runs a double-ended iterator in either [`Order`], where `Descending` iterates
+/// it from the back.
+#[derive(Debug, Clone)]
+pub struct Directed<I> {
+    /// `None` for an empty iterator.
+    iter: Option<I>,
+    order: Order,
+}
+
+impl<I: DoubleEndedIterator> Directed<I> {
+    pub const fn new(iter: I, order: Order) -> Self {
+        Self {
+            iter: Some(iter),
+            order,
+        }
+    }
+
+    pub const fn empty(order: Order) -> Self {
+        Self { iter: None, order }
+    }
+}
+
+impl<I: DoubleEndedIterator> Iterator for Directed<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let iter = self.iter.as_mut()?;
+        match self.order {
+            Order::Ascending => iter.next(),
+            Order::Descending => iter.next_back(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.as_ref().map_or((0, Some(0)), I::size_hint)
+    }
+}
+
+impl<I: DoubleEndedIterator> DoubleEndedIterator for Directed<I> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let iter = self.iter.as_mut()?;
+        match self.order {
+            Order::Ascending => iter.next_back(),
+            Order::Descending => iter.next(),
+        }
+    }
 }
 
 impl Storage for std::collections::HashMap<Vec<u8>, Vec<u8>> {
-    fn get_raw(&self, key: &[u8]) -> Option<Vec<u8>> {
-        self.get(key).cloned()
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        Ok(self.get(key).map(|value| Cow::Borrowed(value.as_slice())))
     }
 }
 
 impl StorageMut for std::collections::HashMap<Vec<u8>, Vec<u8>> {
-    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
         self.insert(key, value);
+        Ok(())
     }
 
-    fn delete_raw(&mut self, key: &[u8]) {
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
         self.remove(key);
+        Ok(())
+    }
+
+    /// Scans every entry, since a `HashMap` has no order to seek by.
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        let range = (low, high);
+        self.retain(|key, _| !range.contains(key));
+        Ok(())
     }
 }
 
 impl Storage for std::collections::BTreeMap<Vec<u8>, Vec<u8>> {
-    fn get_raw(&self, key: &[u8]) -> Option<Vec<u8>> {
-        self.get(key).cloned()
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        Ok(self.get(key).map(|value| Cow::Borrowed(value.as_slice())))
     }
 }
 
 impl StorageMut for std::collections::BTreeMap<Vec<u8>, Vec<u8>> {
-    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
         self.insert(key, value);
+        Ok(())
     }
 
-    fn delete_raw(&mut self, key: &[u8]) {
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
         self.remove(key);
+        Ok(())
+    }
+
+    /// Ranges open at either end are cut off with `split_off` in O(log n), plus
+    /// the cost of dropping the removed entries. Other ranges remove each key in
+    /// turn, in O(k log n) for k removed keys.
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        // BTreeMap::range panics if low > high or low == high, with Bound::Excluded
+        if is_empty_range(&low, &high) {
+            return Ok(());
+        }
+        match (low, high) {
+            (Bound::Unbounded, Bound::Unbounded) => self.clear(),
+            (low, Bound::Unbounded) => {
+                split_off_after(self, low);
+            }
+            (Bound::Unbounded, high) => {
+                let mut removed = std::mem::take(self);
+                *self = split_off_after(&mut removed, flip_bound(high));
+            }
+            (low, high) => {
+                let keys: Vec<_> = self.range((low, high)).map(|(k, _)| k.clone()).collect();
+                for key in keys {
+                    self.remove(&key);
+                }
+            }
+        }
+        Ok(())
     }
 }
 
-macro_rules! encode_bound {
-    ($bound:expr) => {
-        match $bound {
-            Bound::Included(k) => Bound::Included(k.encode()?),
-            Bound::Excluded(k) => Bound::Excluded(k.encode()?),
-            Bound::Unbounded => Bound::Unbounded,
+/// Splits off and returns the entries of `map` within `(low, Unbounded)`.
+fn split_off_after(
+    map: &mut std::collections::BTreeMap<Vec<u8>, Vec<u8>>,
+    low: Bound<Vec<u8>>,
+) -> std::collections::BTreeMap<Vec<u8>, Vec<u8>> {
+    match low {
+        Bound::Included(key) => map.split_off(&key),
+        Bound::Excluded(key) => {
+            let mut tail = map.split_off(&key);
+            if let Some(value) = tail.remove(&key) {
+                map.insert(key, value);
+            }
+            tail
         }
+        Bound::Unbounded => std::mem::take(map),
+    }
+}
+
+/// Turns the upper bound of a range into the lower bound of its complement.
+fn flip_bound(bound: Bound<Vec<u8>>) -> Bound<Vec<u8>> {
+    match bound {
+        Bound::Included(key) => Bound::Excluded(key),
+        Bound::Excluded(key) => Bound::Included(key),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Deletes every key between `low` and `high` by scanning the range and deleting
+/// the keys one by one. A fallback [`StorageMut::delete_range_raw`] for backends
+/// without a native range delete.
+pub fn scan_delete_range<S: StorageMut + IterableStorage>(
+    storage: &mut S,
+    low: Bound<Vec<u8>>,
+    high: Bound<Vec<u8>>,
+) -> Result<(), BackendError> {
+    let (low, high) = (low.map(KeyType::<()>::Raw), high.map(KeyType::Raw));
+    let keys = match storage.keys(low, high, Order::Ascending) {
+        Ok(keys) => keys,
+        Err(RawStorageError::KeySerialize(e)) => match e {},
+        Err(RawStorageError::Backend(e)) => return Err(e),
     };
+    let keys = keys
+        .map(|key| key.map(Cow::into_owned))
+        .collect::<Result<Vec<_>, _>>()?;
+    for key in keys {
+        storage.delete_raw(&key)?;
+    }
+    Ok(())
 }
 
-fn is_empty_range(start: &Bound<Vec<u8>>, end: &Bound<Vec<u8>>) -> bool {
+/// Returns the smallest byte string greater than every byte string starting with
+/// `prefix`, or `None` if there is no such string (`prefix` is empty or all `0xFF`).
+pub(crate) fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|&byte| byte != 0xFF)?;
+    let mut successor = prefix[..=last].to_vec();
+    successor[last] += 1;
+    Some(successor)
+}
+
+/// The encoded bounds of a range of keys.
+pub(crate) type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// Returns the bounds of the range of keys starting with `prefix`.
+pub(crate) fn prefix_range(prefix: &[u8]) -> KeyRange {
+    let high = prefix_successor(prefix).map_or(Bound::Unbounded, Bound::Excluded);
+    (Bound::Included(prefix.to_vec()), high)
+}
+
+pub(crate) fn is_empty_range(start: &Bound<Vec<u8>>, end: &Bound<Vec<u8>>) -> bool {
     match (start, end) {
         // If one bound is Included, then start must be strictly greater than end
         // for the range to be empty.
@@ -113,34 +466,115 @@ fn is_empty_range(start: &Bound<Vec<u8>>, end: &Bound<Vec<u8>>) -> bool {
     }
 }
 
-fn clone_kv((k, v): (&Vec<u8>, &Vec<u8>)) -> (Vec<u8>, Vec<u8>) {
-    (k.clone(), v.clone())
+/// Drops the value of an entry. Backends map their [`IterableStorage::Iter`]
+/// through it to implement [`IterableStorage::Keys`].
+pub(crate) fn key_of(entry: KvResult<'_>) -> KeyResult<'_> {
+    entry.map(|(key, _)| key)
 }
 
-fn clone_k((k, _): (&Vec<u8>, &Vec<u8>)) -> Vec<u8> {
-    k.clone()
+/// An owned page of entries, as fetched by a [`PagedScan`].
+#[cfg(any(feature = "sqlite", feature = "lmdb", feature = "remote"))]
+pub(crate) type Page = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The state of a scan that fetches its range a page at a time, for backends
+/// that cannot lend out a cursor. Each page resumes after the last entry of the
+/// one before, so the scan sees writes made between pages.
+#[cfg(any(feature = "sqlite", feature = "lmdb", feature = "remote"))]
+pub(crate) struct PagedScan {
+    /// The bounds of the entries not fetched yet.
+    low: Bound<Vec<u8>>,
+    high: Bound<Vec<u8>>,
+    order: Order,
+    page: VecDeque<(Vec<u8>, Vec<u8>)>,
+    done: bool,
+}
+
+#[cfg(any(feature = "sqlite", feature = "lmdb", feature = "remote"))]
+impl PagedScan {
+    pub(crate) fn new(low: Bound<Vec<u8>>, high: Bound<Vec<u8>>, order: Order) -> Self {
+        Self {
+            done: is_empty_range(&low, &high),
+            low,
+            high,
+            order,
+            page: VecDeque::new(),
+        }
+    }
+
+    /// Returns the next entry, first refilling an empty page with `fetch`.
+    ///
+    /// `fetch` is given the bounds and order of the entries not fetched yet, and
+    /// returns the first of them along with whether they were the last. The scan
+    /// ends after the first error.
+    pub(crate) fn next<'a>(
+        &mut self,
+        fetch: impl FnOnce(
+            &Bound<Vec<u8>>,
+            &Bound<Vec<u8>>,
+            Order,
+        ) -> Result<(Page, bool), BackendError>,
+    ) -> Option<KvResult<'a>> {
+        if self.page.is_empty() && !self.done {
+            match fetch(&self.low, &self.high, self.order) {
+                Ok((page, last)) => {
+                    self.done = last;
+                    self.page.extend(page);
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+            // The next page resumes after the last entry of this one
+            if let Some((last, _)) = self.page.back() {
+                match self.order {
+                    Order::Ascending => self.low = Bound::Excluded(last.clone()),
+                    Order::Descending => self.high = Bound::Excluded(last.clone()),
+                }
+            }
+        }
+        let (key, value) = self.page.pop_front()?;
+        Some(Ok((Cow::Owned(key), Cow::Owned(value))))
+    }
+}
+
+fn borrow_kv<'a>((k, v): (&'a Vec<u8>, &'a Vec<u8>)) -> KvResult<'a> {
+    Ok((Cow::Borrowed(k), Cow::Borrowed(v)))
+}
+
+fn borrow_k<'a>((k, _): (&'a Vec<u8>, &'a Vec<u8>)) -> KeyResult<'a> {
+    Ok(Cow::Borrowed(k))
+}
+
+type BTreeMapEntry<'a> = (&'a Vec<u8>, &'a Vec<u8>);
+type BTreeMapRange<'a> = Directed<std::collections::btree_map::Range<'a, Vec<u8>, Vec<u8>>>;
+
+fn btree_map_range<K: Encodable<KeyEncoding>>(
+    map: &std::collections::BTreeMap<Vec<u8>, Vec<u8>>,
+    low: Bound<K>,
+    high: Bound<K>,
+    order: Order,
+) -> Result<BTreeMapRange<'_>, RawStorageError> {
+    let low = encode_bound!(low);
+    let high = encode_bound!(high);
+    // BTreeMap::range panics if low > high or low == high, with Bound::Excluded
+    if is_empty_range(&low, &high) {
+        return Ok(Directed::empty(order));
+    }
+    Ok(Directed::new(map.range((low, high)), order))
 }
 
 impl IterableStorage for std::collections::BTreeMap<Vec<u8>, Vec<u8>> {
+    type Keys<'a> = std::iter::Map<BTreeMapRange<'a>, fn(BTreeMapEntry<'a>) -> KeyResult<'a>>;
+    type Iter<'a> = std::iter::Map<BTreeMapRange<'a>, fn(BTreeMapEntry<'a>) -> KvResult<'a>>;
+
     fn keys<K: Encodable<KeyEncoding>>(
         &self,
         low: Bound<K>,
         high: Bound<K>,
         order: Order,
-    ) -> Result<Iter<Vec<u8>>, KeySerializeError> {
-        let low = encode_bound!(low);
-        let high = encode_bound!(high);
-
-        // BTreeMap::range panics if low > high or low == high, with Bound::Excluded
-        if is_empty_range(&low, &high) {
-            return Ok(Box::new(std::iter::empty()));
-        }
-
-        let iter = self.range((low, high));
-        match order {
-            Order::Ascending => Ok(Box::new(iter.map(clone_k))),
-            Order::Descending => Ok(Box::new(iter.rev().map(clone_k))),
-        }
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        Ok(btree_map_range(self, low, high, order)?.map(borrow_k as fn(_) -> _))
     }
 
     fn iter<K: Encodable<KeyEncoding>>(
@@ -148,18 +582,49 @@ impl IterableStorage for std::collections::BTreeMap<Vec<u8>, Vec<u8>> {
         low: Bound<K>,
         high: Bound<K>,
         order: Order,
-    ) -> Result<Iter<(Vec<u8>, Vec<u8>)>, KeySerializeError> {
-        let low = encode_bound!(low);
-        let high = encode_bound!(high);
-        // BTreeMap::range panics if low > high or low == high, with Bound::Excluded
-        if is_empty_range(&low, &high) {
-            return Ok(Box::new(std::iter::empty()));
-        }
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        Ok(btree_map_range(self, low, high, order)?.map(borrow_kv as fn(_) -> _))
+    }
+}
 
-        let iter = self.range((low, high));
-        match order {
-            Order::Ascending => Ok(Box::new(iter.map(clone_kv))),
-            Order::Descending => Ok(Box::new(iter.rev().map(clone_kv))),
-        }
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+
+    use crate::KeyType;
+
+    use super::*;
+
+    #[test]
+    fn test_reads_borrow() {
+        let mut btree = BTreeMap::new();
+        btree.set_raw(b"key".to_vec(), b"value".to_vec()).unwrap();
+        assert!(matches!(
+            btree.get_raw(b"key"),
+            Ok(Some(Cow::Borrowed(b"value")))
+        ));
+
+        let mut hash = HashMap::new();
+        hash.set_raw(b"key".to_vec(), b"value".to_vec()).unwrap();
+        assert!(matches!(
+            hash.get_raw(b"key"),
+            Ok(Some(Cow::Borrowed(b"value")))
+        ));
+
+        let (low, high) = (Bound::<KeyType<()>>::Unbounded, Bound::Unbounded);
+        let mut iter = IterableStorage::iter(&btree, low, high, Order::Ascending).unwrap();
+        assert!(matches!(
+            iter.next(),
+            Some(Ok((Cow::Borrowed(b"key"), Cow::Borrowed(b"value"))))
+        ));
+    }
+
+    #[test]
+    fn test_prefix_successor() {
+        assert_eq!(prefix_successor(b""), None);
+        assert_eq!(prefix_successor(b"\xff\xff"), None);
+        assert_eq!(prefix_successor(b"foo"), Some(b"fop".to_vec()));
+        assert_eq!(prefix_successor(b"fo\xff"), Some(b"fp".to_vec()));
+        assert_eq!(prefix_successor(b"\x00\xff\xff"), Some(b"\x01".to_vec()));
     }
 }
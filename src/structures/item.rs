@@ -1,8 +1,10 @@
 use std::{borrow::Cow, marker::PhantomData};
 
+#[cfg(feature = "async")]
+use crate::{AsyncStorage, AsyncStorageMut};
 use crate::{
     Codec, DataStructure, Encodable, Encoding, KeyEncoding, KeyType, Storage, StorageError,
-    StorageMut, Terminal,
+    StorageMut, Terminal, WriteBatch,
 };
 
 pub struct Item<'a, V: Codec<Enc>, Enc: Encoding, K: Codec<KeyEncoding> = Cow<'a, [u8]>>(
@@ -38,29 +40,82 @@ impl<'a, V: Codec<Enc>, Enc: Encoding, K: Codec<KeyEncoding>> Item<'a, V, Enc, K
         Self(KeyType::Key(key), PhantomData)
     }
 
-    pub fn may_load<S: Storage>(&self, storage: &S) -> Result<Option<V>, StorageError<Enc>> {
-        let bytes = storage.get(&self.0)?;
-        let value = bytes.map(|b| V::decode(&mut b.as_slice())).transpose();
+    /// Encodes the key and `value` of a save.
+    fn encode_entry(&self, value: &V) -> Result<(Vec<u8>, Vec<u8>), StorageError<Enc>> {
+        let key = self.0.encode()?;
+        let value = value.encode().map_err(StorageError::ValueSerialize)?;
+        Ok((key, value))
+    }
+
+    fn decode_value(bytes: Option<Cow<'_, [u8]>>) -> Result<Option<V>, StorageError<Enc>> {
+        let value = bytes.map(|b| V::decode(&mut b.as_ref())).transpose();
         value.map_err(StorageError::ValueDeserialize)
     }
 
+    pub fn may_load<S: Storage>(&self, storage: &S) -> Result<Option<V>, StorageError<Enc>> {
+        Self::decode_value(storage.get(&self.0)?)
+    }
+
     pub fn save<S: StorageMut>(&self, storage: &mut S, value: &V) -> Result<(), StorageError<Enc>> {
-        let key = self.0.encode()?;
-        let value = value.encode().map_err(StorageError::ValueSerialize)?;
-        storage.set_raw(key, value);
-        Ok(())
+        let (key, value) = self.encode_entry(value)?;
+        Ok(storage.set_raw(key, value)?)
     }
 
     pub fn delete<S: StorageMut>(&self, storage: &mut S) -> Result<(), StorageError<Enc>> {
         Ok(storage.delete(&self.0)?)
     }
+
+    /// Stages a [`Item::save`] into `batch`.
+    pub fn stage_save(&self, batch: &mut WriteBatch, value: &V) -> Result<(), StorageError<Enc>> {
+        let (key, value) = self.encode_entry(value)?;
+        batch.put(key, value);
+        Ok(())
+    }
+
+    /// Stages a [`Item::delete`] into `batch`.
+    pub fn stage_delete(&self, batch: &mut WriteBatch) -> Result<(), StorageError<Enc>> {
+        batch.delete(self.0.encode()?);
+        Ok(())
+    }
+
+    /// Async variant of [`Item::may_load`].
+    #[cfg(feature = "async")]
+    pub async fn may_load_async<S: AsyncStorage>(
+        &self,
+        storage: &S,
+    ) -> Result<Option<V>, StorageError<Enc>> {
+        Self::decode_value(storage.get(&self.0).await?)
+    }
+
+    /// Async variant of [`Item::save`].
+    #[cfg(feature = "async")]
+    pub async fn save_async<S: AsyncStorageMut>(
+        &self,
+        storage: &mut S,
+        value: &V,
+    ) -> Result<(), StorageError<Enc>> {
+        let (key, value) = self.encode_entry(value)?;
+        Ok(storage.set_raw(key, value).await?)
+    }
+
+    /// Async variant of [`Item::delete`].
+    #[cfg(feature = "async")]
+    pub async fn delete_async<S: AsyncStorageMut>(
+        &self,
+        storage: &mut S,
+    ) -> Result<(), StorageError<Enc>> {
+        Ok(storage.delete(&self.0).await?)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
 
-    use crate::mock::DisplayEncoding;
+    use crate::{
+        mock::{DisplayEncoding, FailingStorage},
+        BackendError,
+    };
 
     use super::*;
 
@@ -85,4 +140,20 @@ mod test {
         assert_eq!(ITEM.may_load(&storage), Ok(Some("baz".to_string())));
         assert_eq!(item.may_load(&storage), Ok(None));
     }
+
+    #[test]
+    fn test_backend_error() {
+        const ITEM: Item<String, DisplayEncoding> = Item::new(b"foo");
+        let mut storage = FailingStorage::default();
+        ITEM.save(&mut storage, &"bar".to_string()).unwrap();
+
+        storage.failing = true;
+        let error = || StorageError::Backend(BackendError::new("disk on fire"));
+        assert_eq!(ITEM.may_load(&storage), Err(error()));
+        assert_eq!(ITEM.save(&mut storage, &"baz".to_string()), Err(error()));
+        assert_eq!(ITEM.delete(&mut storage), Err(error()));
+
+        storage.failing = false;
+        assert_eq!(ITEM.may_load(&storage), Ok(Some("bar".to_string())));
+    }
 }
@@ -1,12 +1,15 @@
 use std::{borrow::Cow, marker::PhantomData, ops::Bound};
 
 use crate::{
-    Codec, DataStructure, DsIter, IterableStorage, KeyEncoding, KeySerializeError, NonTerminal,
-    Order,
+    storage::{prefix_range, KeyRange},
+    Codec, DataStructure, DsIter, IterableStorage, KeyEncoding, KeySerializeError, KeyType,
+    Namespace, NonTerminal, Order, StorageError, StorageMut, WriteBatch,
 };
+#[cfg(feature = "async")]
+use crate::{AsyncIterableStorage, DsStream};
 
 pub struct Map<'a, K: Codec<KeyEncoding>, V: DataStructure> {
-    prefix: Cow<'a, [u8]>,
+    namespace: Namespace<'a>,
     _marker: PhantomData<(K, V)>,
 }
 impl<'a, K: Codec<KeyEncoding>, V: DataStructure> DataStructure for Map<'a, K, V> {
@@ -17,20 +20,31 @@ impl<'a, K: Codec<KeyEncoding>, V: DataStructure> DataStructure for Map<'a, K, V
 
     fn with_prefix(prefix: Vec<u8>) -> Self {
         Self {
-            prefix: Cow::Owned(prefix),
+            namespace: Namespace::Raw(Cow::Owned(prefix)),
             _marker: PhantomData,
         }
     }
 
     fn should_skip_key(key: &Self::Key) -> bool {
-        key.1.as_ref().map_or(false, V::should_skip_key)
+        key.1.as_ref().is_some_and(V::should_skip_key)
     }
 }
 
 impl<K: Codec<KeyEncoding>, V: DataStructure> Map<'static, K, V> {
+    /// Creates a map in the length-delimited namespace `key`.
     pub const fn new(key: &'static [u8]) -> Self {
         Self {
-            prefix: Cow::Borrowed(key),
+            namespace: Namespace::Delimited(Cow::Borrowed(key)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a map whose keys are prefixed by `key` verbatim. This is the
+    /// original layout, in which maps named e.g. `b"foo"` and `b"foob"` can
+    /// overlap: only use it to access existing data.
+    pub const fn new_raw(key: &'static [u8]) -> Self {
+        Self {
+            namespace: Namespace::Raw(Cow::Borrowed(key)),
             _marker: PhantomData,
         }
     }
@@ -39,38 +53,140 @@ impl<K: Codec<KeyEncoding>, V: DataStructure> Map<'static, K, V> {
 impl<'a, K: Codec<KeyEncoding>, V: DataStructure> Map<'a, K, V> {
     fn key(&self, key: &K) -> Result<Vec<u8>, KeySerializeError> {
         let encoded = key.encode()?;
-        let full = [self.prefix.as_ref(), &encoded].concat();
+        let full = [self.prefix().as_ref(), &encoded].concat();
 
         Ok(full)
     }
 
-    pub fn prefix(&self) -> &[u8] {
-        self.prefix.as_ref()
+    pub fn prefix(&self) -> Cow<'_, [u8]> {
+        self.namespace.prefix()
     }
 
     pub fn at(&self, key: impl Into<K>) -> Result<V, KeySerializeError> {
         Ok(V::with_prefix(self.key(&key.into())?))
     }
 
+    /// Iterates over the entries of this map with keys between `start` and `end`.
+    ///
+    /// Unbounded ends are confined to the keys under this map's prefix, so the
+    /// scan never visits keys of other structures.
     pub fn range<'b, S: IterableStorage>(
         &self,
         storage: &'b S,
         start: Bound<K>,
         end: Bound<K>,
         order: Order,
-    ) -> Result<DsIter<'b, Self>, KeySerializeError> {
+    ) -> Result<DsIter<'b, Self, S::Iter<'b>>, StorageError<V::Enc>> {
+        let prefix = self.prefix();
+        let iter = match (start, end) {
+            (Bound::Unbounded, Bound::Unbounded) => storage.prefix_iter(&prefix, order)?,
+            (start, end) => {
+                let (start, end) = self.raw_range(start, end)?;
+                storage.iter(start.map(KeyType::<K>::Raw), end.map(KeyType::Raw), order)?
+            }
+        };
+        Ok(DsIter::new(prefix.to_vec(), iter))
+    }
+
+    /// Async variant of [`Map::range`], returning a stream of the entries.
+    #[cfg(feature = "async")]
+    pub fn range_async<'b, S: AsyncIterableStorage>(
+        &self,
+        storage: &'b S,
+        start: Bound<K>,
+        end: Bound<K>,
+        order: Order,
+    ) -> Result<DsStream<'b, Self, S::Iter<'b>>, StorageError<V::Enc>> {
+        let prefix = self.prefix();
+        let stream = match (start, end) {
+            (Bound::Unbounded, Bound::Unbounded) => storage.prefix_iter(&prefix, order),
+            (start, end) => {
+                let (start, end) = self.raw_range(start, end)?;
+                storage.iter_raw(start, end, order)
+            }
+        };
+        Ok(DsStream::new(prefix.to_vec(), stream))
+    }
+
+    /// Encodes the bounds of a range, confining unbounded ends to this map's
+    /// prefix.
+    fn raw_range(&self, start: Bound<K>, end: Bound<K>) -> Result<KeyRange, KeySerializeError> {
+        let (prefix_start, prefix_end) = prefix_range(&self.prefix());
         let start = match start {
             Bound::Included(k) => Bound::Included(self.key(&k)?),
             Bound::Excluded(k) => Bound::Excluded(self.key(&k)?),
-            Bound::Unbounded => Bound::Unbounded,
+            Bound::Unbounded => prefix_start,
         };
         let end = match end {
             Bound::Included(k) => Bound::Included(self.key(&k)?),
             Bound::Excluded(k) => Bound::Excluded(self.key(&k)?),
-            Bound::Unbounded => Bound::Unbounded,
+            Bound::Unbounded => prefix_end,
         };
-        let iter = storage.iter(start, end, order)?;
-        Ok(DsIter::new(self.prefix.to_vec(), iter))
+        Ok((start, end))
+    }
+
+    /// Moves every entry of this map under `target`, re-encoding each key as a
+    /// key of type `K2`. Entries of nested structures are moved along with their
+    /// outer key. Returns the number of storage entries moved.
+    ///
+    /// This is the migration path between key layouts, e.g. from `String` keys to
+    /// [`crate::Lexicographic<String>`] keys, or from a [`Map::new_raw`] map to a
+    /// [`Map::new`] map with `K2 = K`. `target` may share this map's prefix.
+    /// Every entry under this map's prefix must be encoded with `K`'s layout.
+    /// All moves are applied as a single [`WriteBatch`].
+    pub fn migrate_keys<K2, S>(
+        &self,
+        storage: &mut S,
+        target: &Map<'_, K2, V>,
+    ) -> Result<usize, StorageError<V::Enc>>
+    where
+        K: Into<K2>,
+        K2: Codec<KeyEncoding>,
+        S: StorageMut + IterableStorage,
+    {
+        let prefix = self.prefix();
+        let entries = storage
+            .prefix_iter(&prefix, Order::Ascending)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut migrated = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let mut rest = &key[prefix.len()..];
+            let decoded = K::decode(&mut rest)?;
+            let new_key = [target.key(&decoded.into())?.as_slice(), rest].concat();
+            migrated.push((key.into_owned(), new_key, value.into_owned()));
+        }
+
+        // Delete everything first, since old and new keys may overlap.
+        let mut batch = WriteBatch::new();
+        for (key, _, _) in &migrated {
+            batch.delete(key.clone());
+        }
+        let count = migrated.len();
+        for (_, key, value) in migrated {
+            batch.put(key, value);
+        }
+        storage.write_batch(batch)?;
+        Ok(count)
+    }
+
+    /// Deletes every entry of this map, including the entries of nested
+    /// structures, with a single range delete.
+    pub fn clear<S: StorageMut>(&self, storage: &mut S) -> Result<(), StorageError<V::Enc>> {
+        let (low, high) = prefix_range(&self.prefix());
+        Ok(storage.delete_range_raw(low, high)?)
+    }
+
+    /// Deletes the entry at `key`, along with every entry of the structure nested
+    /// under `key`, with a single range delete. Entries at other keys are left
+    /// untouched, since no encoded key is a prefix of another.
+    pub fn remove_prefix<S: StorageMut>(
+        &self,
+        storage: &mut S,
+        key: impl Into<K>,
+    ) -> Result<(), StorageError<V::Enc>> {
+        let (low, high) = prefix_range(&self.key(&key.into())?);
+        Ok(storage.delete_range_raw(low, high)?)
     }
 }
 
@@ -78,7 +194,10 @@ impl<'a, K: Codec<KeyEncoding>, V: DataStructure> Map<'a, K, V> {
 mod test {
     use std::collections::{BTreeMap, HashMap};
 
-    use crate::{mock::DisplayEncoding, Item};
+    use crate::{
+        mock::{DisplayEncoding, FailingStorage},
+        BackendError, Item,
+    };
 
     use super::*;
 
@@ -135,4 +254,339 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_lexicographic_keys() {
+        use crate::Lexicographic;
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        const OLD: Map<String, Item<String, DisplayEncoding>> = Map::new(b"users");
+        const NEW: Map<Lexicographic<String>, Item<String, DisplayEncoding>> = Map::new(b"users");
+
+        for name in ["b", "ab", "abc", "c", "a"] {
+            OLD.at(name)
+                .unwrap()
+                .save(&mut storage, &name.to_uppercase())
+                .unwrap();
+        }
+
+        // Length-prefixed keys sort by length first
+        let names: Vec<_> = OLD
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().0 .0)
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c", "ab", "abc"]);
+
+        assert_eq!(OLD.migrate_keys(&mut storage, &NEW), Ok(5));
+        assert_eq!(storage.len(), 5);
+
+        let names: Vec<_> = NEW
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().0 .0.into_inner())
+            .collect();
+        assert_eq!(names, vec!["a", "ab", "abc", "b", "c"]);
+        assert_eq!(
+            NEW.at("ab").unwrap().may_load(&storage),
+            Ok(Some("AB".to_string()))
+        );
+
+        // Prefix scan: every key starting with "ab"
+        let names: Vec<_> = NEW
+            .range(
+                &storage,
+                Bound::Included("ab".into()),
+                Bound::Excluded("ac".into()),
+                Order::Descending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(names, vec!["ABC", "AB"]);
+    }
+
+    #[test]
+    fn test_migrate_nested_keys() {
+        use crate::Lexicographic;
+
+        type Inner = Map<'static, u8, Item<'static, u8, DisplayEncoding>>;
+        const OLD: Map<Vec<u8>, Inner> = Map::new(b"old");
+        const NEW: Map<Lexicographic<Vec<u8>>, Inner> = Map::new(b"new");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for outer in [vec![0u8], vec![1, 0], vec![0xff]] {
+            for inner in 0..3u8 {
+                let item = OLD.at(outer.clone()).unwrap().at(inner).unwrap();
+                item.save(&mut storage, &inner).unwrap();
+            }
+        }
+
+        assert_eq!(OLD.migrate_keys(&mut storage, &NEW), Ok(9));
+        let entries: Vec<_> = NEW
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| {
+                let ((outer, inner), value) = res.unwrap();
+                (outer.into_inner(), inner.unwrap().0, value)
+            })
+            .collect();
+        assert_eq!(entries.len(), 9);
+        assert_eq!(entries[0], (vec![0], 0, 0));
+        assert_eq!(entries[3], (vec![1, 0], 0, 0));
+        assert_eq!(entries[8], (vec![0xff], 2, 2));
+    }
+
+    #[test]
+    fn test_namespace_isolation() {
+        const FOO: Map<u8, Item<String, DisplayEncoding>> = Map::new(b"foo");
+        const FOOB: Map<u8, Item<String, DisplayEncoding>> = Map::new(b"foob");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        FOO.at(1)
+            .unwrap()
+            .save(&mut storage, &"foo".to_string())
+            .unwrap();
+        FOOB.at(1)
+            .unwrap()
+            .save(&mut storage, &"foob".to_string())
+            .unwrap();
+
+        let collect = |map: &Map<u8, Item<String, DisplayEncoding>>, storage| {
+            map.range(
+                storage,
+                Bound::Included(0),
+                Bound::Included(u8::MAX),
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(collect(&FOO, &storage), vec!["foo"]);
+        assert_eq!(collect(&FOOB, &storage), vec!["foob"]);
+
+        // A namespace is encoded like the key of an `Item` with the same name
+        assert_eq!(FOO.prefix().as_ref(), b"\x03foo");
+    }
+
+    #[test]
+    fn test_raw_namespace() {
+        const RAW: Map<u8, Item<String, DisplayEncoding>> = Map::new_raw(b"foo");
+        const DELIMITED: Map<u8, Item<String, DisplayEncoding>> = Map::new(b"foo");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        RAW.at(1)
+            .unwrap()
+            .save(&mut storage, &"one".to_string())
+            .unwrap();
+        assert_eq!(storage.keys().next().unwrap(), b"foo\x01");
+
+        assert_eq!(RAW.migrate_keys(&mut storage, &DELIMITED), Ok(1));
+        assert_eq!(RAW.at(1).unwrap().may_load(&storage), Ok(None));
+        assert_eq!(
+            DELIMITED.at(1).unwrap().may_load(&storage),
+            Ok(Some("one".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_range_confined_to_prefix() {
+        const BEFORE: Item<String, DisplayEncoding> = Item::new(b"a");
+        const MAP: Map<u8, Item<u8, DisplayEncoding>> = Map::new(b"b");
+        const AFTER: Item<String, DisplayEncoding> = Item::new(b"c");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        BEFORE.save(&mut storage, &"before".to_string()).unwrap();
+        AFTER.save(&mut storage, &"after".to_string()).unwrap();
+        for i in 0..3 {
+            MAP.at(i).unwrap().save(&mut storage, &i).unwrap();
+        }
+
+        let collect = |start, end, order| {
+            MAP.range(&storage, start, end, order)
+                .unwrap()
+                .map(|res| res.unwrap().1)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            collect(Bound::Unbounded, Bound::Unbounded, Order::Ascending),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            collect(Bound::Unbounded, Bound::Unbounded, Order::Descending),
+            vec![2, 1, 0]
+        );
+        assert_eq!(
+            collect(Bound::Excluded(0), Bound::Unbounded, Order::Descending),
+            vec![2, 1]
+        );
+        assert_eq!(
+            collect(Bound::Unbounded, Bound::Excluded(2), Order::Ascending),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn test_clear_and_remove_prefix() {
+        type Inner = Map<'static, u8, Item<'static, u8, DisplayEncoding>>;
+        const BEFORE: Item<String, DisplayEncoding> = Item::new(b"a");
+        const MAP: Map<u8, Inner> = Map::new(b"b");
+        const AFTER: Item<String, DisplayEncoding> = Item::new(b"c");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        BEFORE.save(&mut storage, &"before".to_string()).unwrap();
+        AFTER.save(&mut storage, &"after".to_string()).unwrap();
+        for outer in [0, 1, 2, u8::MAX] {
+            for inner in 0..3 {
+                let item = MAP.at(outer).unwrap().at(inner).unwrap();
+                item.save(&mut storage, &inner).unwrap();
+            }
+        }
+
+        let outer_keys = |storage: &BTreeMap<Vec<u8>, Vec<u8>>| {
+            MAP.range(
+                storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().0 .0)
+            .collect::<Vec<_>>()
+        };
+
+        MAP.remove_prefix(&mut storage, 1).unwrap();
+        MAP.remove_prefix(&mut storage, u8::MAX).unwrap();
+        assert_eq!(outer_keys(&storage), vec![0, 0, 0, 2, 2, 2]);
+
+        MAP.clear(&mut storage).unwrap();
+        assert_eq!(outer_keys(&storage), Vec::<u8>::new());
+        assert_eq!(storage.len(), 2);
+        assert_eq!(BEFORE.may_load(&storage), Ok(Some("before".to_string())));
+        assert_eq!(AFTER.may_load(&storage), Ok(Some("after".to_string())));
+    }
+
+    #[test]
+    fn test_range_prefix_ending_in_ff() {
+        type Inner = Map<'static, u8, Item<'static, u8, DisplayEncoding>>;
+        const MAP: Map<u8, Inner> = Map::new(b"m");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for outer in [0xfe, 0xff] {
+            for inner in [0, 0xff] {
+                let item = MAP.at(outer).unwrap().at(inner).unwrap();
+                item.save(&mut storage, &inner).unwrap();
+            }
+        }
+
+        let inner = MAP.at(0xfe).unwrap();
+        let values: Vec<_> = inner
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Descending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(values, vec![0xff, 0]);
+
+        let inner = MAP.at(0xff).unwrap();
+        let values: Vec<_> = inner
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(values, vec![0, 0xff]);
+    }
+
+    #[test]
+    fn test_range_backend_error() {
+        const MAP: Map<u8, Item<String, DisplayEncoding>> = Map::new(b"map");
+        let mut storage = FailingStorage::default();
+        for i in 0..3 {
+            MAP.at(i)
+                .unwrap()
+                .save(&mut storage, &i.to_string())
+                .unwrap();
+        }
+
+        storage.failing = true;
+        let entries: Vec<_> = MAP
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.map(|((key, _), value)| (key, value)))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                Ok((0, "0".to_string())),
+                Err(StorageError::Backend(BackendError::new("disk on fire")))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range_double_ended_and_send() {
+        const MAP: Map<u8, Item<u8, DisplayEncoding>> = Map::new(b"map");
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for i in 0..5 {
+            MAP.at(i).unwrap().save(&mut storage, &i).unwrap();
+        }
+
+        let mut iter = MAP
+            .range(
+                &storage,
+                Bound::Included(1),
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.unwrap().1);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.rev().collect::<Vec<_>>(), vec![3, 2]);
+
+        let iter = MAP
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Descending,
+            )
+            .unwrap();
+        let values = std::thread::scope(|s| {
+            s.spawn(move || iter.map(|res| res.unwrap().1).collect::<Vec<_>>())
+                .join()
+                .unwrap()
+        });
+        assert_eq!(values, vec![4, 3, 2, 1, 0]);
+    }
 }
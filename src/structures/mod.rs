@@ -3,4 +3,9 @@ mod map;
 mod queue;
 mod vector;
 
-pub use {item::Item, map::Map, queue::PriorityQueue /*vector::Vector*/};
+pub use {
+    item::Item,
+    map::Map,
+    queue::{MultiPriorityQueue, PriorityQueue},
+    vector::Vector,
+};
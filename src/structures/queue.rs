@@ -1,7 +1,14 @@
 use crate::{
-    Codec, Encoding, Item, IterableStorage, KeyEncoding, Map, Order, StorageError, StorageMut,
+    decode, storage::prefix_range, Codec, DataStructure, DsResult, Encodable, Encoding, Item,
+    IterableStorage, KeyEncoding, KeySerializeError, Map, Namespace, Order, Storage, StorageError,
+    StorageMut, WriteBatch,
 };
-use std::ops::Bound;
+use std::{borrow::Cow, marker::PhantomData, ops::Bound};
+
+#[cfg(feature = "async")]
+use crate::{async_storage::next, AsyncIterableStorage, AsyncStorageMut};
+#[cfg(feature = "async")]
+use std::pin::pin;
 
 // Single value per priority queue
 pub struct PriorityQueue<'a, K: Codec<KeyEncoding> + Ord + Clone, V: Codec<Enc>, Enc: Encoding> {
@@ -11,12 +18,21 @@ pub struct PriorityQueue<'a, K: Codec<KeyEncoding> + Ord + Clone, V: Codec<Enc>,
 impl<'a, K: Codec<KeyEncoding> + Ord + Clone, V: Codec<Enc>, Enc: Encoding>
     PriorityQueue<'a, K, V, Enc>
 {
+    /// Creates a queue in the length-delimited namespace `prefix`.
     pub const fn new(prefix: &'static [u8]) -> Self {
         Self {
             map: Map::new(prefix),
         }
     }
 
+    /// Creates a queue whose keys are prefixed by `prefix` verbatim. See
+    /// [`Map::new_raw`].
+    pub const fn new_raw(prefix: &'static [u8]) -> Self {
+        Self {
+            map: Map::new_raw(prefix),
+        }
+    }
+
     pub fn push<S: StorageMut>(
         &self,
         storage: &mut S,
@@ -35,8 +51,13 @@ impl<'a, K: Codec<KeyEncoding> + Ord + Clone, V: Codec<Enc>, Enc: Encoding>
             .map
             .range(storage, Bound::Unbounded, Bound::Unbounded, order)?;
 
-        let item = iter.next();
+        Self::first(iter.next())
+    }
 
+    /// Unwraps the first entry of a scan over the queue.
+    fn first(
+        item: Option<DsResult<Map<'a, K, Item<'a, V, Enc>>>>,
+    ) -> Result<Option<(K, V)>, StorageError<Enc>> {
         match item {
             Some(Ok((key, value))) => Ok(Some((key.0, value))),
             Some(Err(e)) => Err(e),
@@ -49,116 +70,349 @@ impl<'a, K: Codec<KeyEncoding> + Ord + Clone, V: Codec<Enc>, Enc: Encoding>
         storage: &mut S,
         order: Order,
     ) -> Result<Option<(K, V)>, StorageError<Enc>> {
-        if let Some((key, value)) = self.peek(storage, order)? {
-            self.map.at(key.clone())?.delete(storage)?;
+        let mut batch = WriteBatch::new();
+        let popped = self.stage_pop(storage, &mut batch, order)?;
+        storage.write_batch(batch)?;
+        Ok(popped)
+    }
+
+    /// Like [`PriorityQueue::pop`], but stages the removal into `batch` instead of
+    /// writing it. Reads do not see `batch`, so staging two pops into the same
+    /// batch returns the same value twice; use a [`crate::Transaction`] for that.
+    pub fn stage_pop<S: IterableStorage>(
+        &self,
+        storage: &S,
+        batch: &mut WriteBatch,
+        order: Order,
+    ) -> Result<Option<(K, V)>, StorageError<Enc>> {
+        let peeked = self.peek(storage, order)?;
+        self.stage_remove(batch, peeked)
+    }
+
+    /// Stages the removal of the `peeked` entry into `batch`, returning it.
+    fn stage_remove(
+        &self,
+        batch: &mut WriteBatch,
+        peeked: Option<(K, V)>,
+    ) -> Result<Option<(K, V)>, StorageError<Enc>> {
+        if let Some((key, value)) = peeked {
+            self.map.at(key.clone())?.stage_delete(batch)?;
             Ok(Some((key, value)))
         } else {
             Ok(None)
         }
     }
+
+    /// Async variant of [`PriorityQueue::push`].
+    #[cfg(feature = "async")]
+    pub async fn push_async<S: AsyncStorageMut>(
+        &self,
+        storage: &mut S,
+        priority: K,
+        value: &V,
+    ) -> Result<(), StorageError<Enc>> {
+        self.map.at(priority)?.save_async(storage, value).await
+    }
+
+    /// Async variant of [`PriorityQueue::peek`].
+    #[cfg(feature = "async")]
+    pub async fn peek_async<S: AsyncIterableStorage>(
+        &self,
+        storage: &S,
+        order: Order,
+    ) -> Result<Option<(K, V)>, StorageError<Enc>> {
+        let stream = self
+            .map
+            .range_async(storage, Bound::Unbounded, Bound::Unbounded, order)?;
+        Self::first(next(pin!(stream)).await)
+    }
+
+    /// Async variant of [`PriorityQueue::pop`].
+    #[cfg(feature = "async")]
+    pub async fn pop_async<S: AsyncStorageMut + AsyncIterableStorage>(
+        &self,
+        storage: &mut S,
+        order: Order,
+    ) -> Result<Option<(K, V)>, StorageError<Enc>> {
+        let peeked = self.peek_async(storage, order).await?;
+        let mut batch = WriteBatch::new();
+        let popped = self.stage_remove(&mut batch, peeked)?;
+        storage.write_batch(batch).await?;
+        Ok(popped)
+    }
+
+    /// Removes every value from the queue with a single range delete.
+    pub fn clear<S: StorageMut>(&self, storage: &mut S) -> Result<(), StorageError<Enc>> {
+        self.map.clear(storage)
+    }
+
+    /// Removes the value at `priority`, if any, with a single range delete.
+    pub fn remove_prefix<S: StorageMut>(
+        &self,
+        storage: &mut S,
+        priority: K,
+    ) -> Result<(), StorageError<Enc>> {
+        self.map.remove_prefix(storage, priority)
+    }
 }
 
-/*
-// Multiple values per priority queue
+/// Namespace under the queue prefix holding the total number of queued values.
+const LEN_NAMESPACE: u8 = 0;
+/// Namespace under the queue prefix holding the next sequence number per priority.
+const COUNTERS_NAMESPACE: u8 = 1;
+/// Namespace under the queue prefix holding the queued values.
+const VALUES_NAMESPACE: u8 = 2;
+
+type Values<K, V, Enc> = Map<'static, K, Map<'static, u64, Item<'static, V, Enc>>>;
+
+// Multiple values per priority queue, FIFO within a priority
+//
+// MultiPriorityQueue has keys
+//      prefix/0 -> total number of values
+//      prefix/1/priority -> next sequence number for priority
+//      prefix/2/priority/sequence -> value
 pub struct MultiPriorityQueue<'a, K, V, Enc>
 where
-    K: KeySerde + Ord + Clone,
-    Enc: Encoding,
+    K: Codec<KeyEncoding> + Ord + Clone,
     V: Codec<Enc>,
-    usize: Codec<Enc>,
+    Enc: Encoding,
 {
-    map: Map<'a, K, Map<'a, usize, Item<'a, V, Enc>>>,
-    counters: Map<'a, K, Item<'a, usize, Enc>>,
+    namespace: Namespace<'a>,
+    _marker: PhantomData<(K, V, Enc)>,
 }
 
-impl<'a, K, V, Enc> MultiPriorityQueue<'a, K, V, Enc>
+impl<K, V, Enc> MultiPriorityQueue<'static, K, V, Enc>
 where
-    K: KeySerde + Ord + Clone,
-    Enc: Encoding,
+    K: Codec<KeyEncoding> + Ord + Clone,
     V: Codec<Enc>,
-    usize: Codec<Enc>,
+    Enc: Encoding,
 {
+    /// Creates a queue in the length-delimited namespace `prefix`.
     pub const fn new(prefix: &'static [u8]) -> Self {
         Self {
-            map: Map::new(prefix),
-            counters: Map::new(b"counters"),
+            namespace: Namespace::Delimited(Cow::Borrowed(prefix)),
+            _marker: PhantomData,
         }
     }
 
+    /// Creates a queue whose keys are prefixed by `prefix` verbatim. See
+    /// [`Map::new_raw`].
+    pub const fn new_raw(prefix: &'static [u8]) -> Self {
+        Self {
+            namespace: Namespace::Raw(Cow::Borrowed(prefix)),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V, Enc> MultiPriorityQueue<'a, K, V, Enc>
+where
+    K: Codec<KeyEncoding> + Ord + Clone,
+    V: Codec<Enc>,
+    Enc: Encoding,
+{
+    fn namespace(&self, namespace: u8) -> Vec<u8> {
+        [self.namespace.prefix().as_ref(), &[namespace]].concat()
+    }
+
+    fn values(&self) -> Values<K, V, Enc> {
+        Map::with_prefix(self.namespace(VALUES_NAMESPACE))
+    }
+
+    fn counter_key(&self, priority: &K) -> Result<Vec<u8>, KeySerializeError> {
+        let encoded = priority.encode()?;
+        Ok([self.namespace(COUNTERS_NAMESPACE), encoded].concat())
+    }
+
+    fn load_counter<S: Storage>(&self, storage: &S, key: &[u8]) -> Result<u64, StorageError<Enc>> {
+        match storage.get_raw(key)? {
+            Some(bytes) => Ok(decode::<u64, KeyEncoding>(&bytes)?),
+            None => Ok(0),
+        }
+    }
+
+    fn stage_counter(
+        &self,
+        batch: &mut WriteBatch,
+        key: Vec<u8>,
+        value: u64,
+    ) -> Result<(), StorageError<Enc>> {
+        if value == 0 {
+            batch.delete(key);
+        } else {
+            batch.put(key, Encodable::<KeyEncoding>::encode(&value)?);
+        }
+        Ok(())
+    }
+
+    /// Returns the total number of values in the queue, across all priorities.
+    pub fn len<S: Storage>(&self, storage: &S) -> Result<u64, StorageError<Enc>> {
+        self.load_counter(storage, &self.namespace(LEN_NAMESPACE))
+    }
+
+    pub fn is_empty<S: Storage>(&self, storage: &S) -> Result<bool, StorageError<Enc>> {
+        self.len(storage).map(|len| len == 0)
+    }
+
+    /// Pushes a value at the given priority. Values with equal priority are
+    /// popped in the order they were pushed.
     pub fn push<S: StorageMut>(
         &self,
         storage: &mut S,
         priority: K,
         value: &V,
     ) -> Result<(), StorageError<Enc>> {
-        // Get or initialize counter
-        let counter = self.counters.at(priority.clone())?;
-        let next_id = counter.may_load(storage)?.unwrap_or(0) + 1;
-        counter.save(storage, &next_id)?;
+        let counter_key = self.counter_key(&priority)?;
+        let sequence = self.load_counter(storage, &counter_key)?;
+        let len = self.len(storage)?;
 
-        // Save value
-        self.map.at(priority)?.at(next_id)?.save(storage, value)
+        let mut batch = WriteBatch::new();
+        self.values()
+            .at(priority)?
+            .at(sequence)?
+            .stage_save(&mut batch, value)?;
+        self.stage_counter(&mut batch, counter_key, sequence + 1)?;
+        self.stage_counter(&mut batch, self.namespace(LEN_NAMESPACE), len + 1)?;
+        Ok(storage.write_batch(batch)?)
     }
 
+    /// Returns the first value at the lowest (`Order::Ascending`) or highest
+    /// (`Order::Descending`) priority, without removing it.
     pub fn peek<S: IterableStorage>(
         &self,
         storage: &S,
-        ascending: bool,
-    ) -> Result<Option<(K, Vec<V>)>, StorageError<Enc>> {
-        let iter = self
-            .map
-            .range(storage, Bound::Unbounded, Bound::Unbounded)?;
+        order: Order,
+    ) -> Result<Option<(K, V)>, StorageError<Enc>> {
+        Ok(self
+            .peek_entry(storage, order)?
+            .map(|(priority, _, value)| (priority, value)))
+    }
 
-        let priority_map = if ascending {
-            iter.into_iter().next()
-        } else {
-            iter.into_iter().last()
+    fn peek_entry<S: IterableStorage>(
+        &self,
+        storage: &S,
+        order: Order,
+    ) -> Result<Option<(K, u64, V)>, StorageError<Enc>> {
+        let priority = match self
+            .values()
+            .range(storage, Bound::Unbounded, Bound::Unbounded, order)?
+            .next()
+        {
+            Some(Ok((key, _))) => key.0,
+            Some(Err(e)) => return Err(e),
+            None => return Ok(None),
         };
 
-        match priority_map {
-            Some(Ok((key, _))) => {
-                let values_iter = self.map.at(key.0.clone())?.range(
-                    storage,
-                    Bound::Unbounded,
-                    Bound::Unbounded,
-                )?;
-
-                let mut values = Vec::new();
-                for item in values_iter {
-                    match item {
-                        Ok((_, value)) => values.push(value),
-                        Err(e) => return Err(e),
-                    }
-                }
-
-                Ok(Some((key.0, values)))
-            }
+        // Within a priority, the oldest value is always the first.
+        let item = self
+            .values()
+            .at(priority.clone())?
+            .range(
+                storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )?
+            .next();
+
+        match item {
+            Some(Ok((key, value))) => Ok(Some((priority, key.0, value))),
             Some(Err(e)) => Err(e),
             None => Ok(None),
         }
     }
 
+    /// Removes and returns exactly one value: the oldest value at the lowest
+    /// (`Order::Ascending`) or highest (`Order::Descending`) priority.
     pub fn pop<S: StorageMut + IterableStorage>(
         &self,
         storage: &mut S,
-        ascending: bool,
-    ) -> Result<Option<(K, Vec<V>)>, StorageError<Enc>> {
-        if let Some((priority, values)) = self.peek(storage, ascending)? {
-            // Clear all values for this priority
-            let priority_map = self.map.at(priority.clone())?;
-            for i in 1..=values.len() as u64 {
-                priority_map.at(i)?.save(storage, &values[i as usize - 1])?;
-            }
-
-            // Reset counter
-            self.counters.at(priority.clone())?.save(storage, &0)?;
-
-            Ok(Some((priority, values)))
-        } else {
-            Ok(None)
+        order: Order,
+    ) -> Result<Option<(K, V)>, StorageError<Enc>> {
+        let mut batch = WriteBatch::new();
+        let popped = self.stage_pop(storage, &mut batch, order)?;
+        storage.write_batch(batch)?;
+        Ok(popped)
+    }
+
+    /// Like [`MultiPriorityQueue::pop`], but stages the removal into `batch`
+    /// instead of writing it. Reads do not see `batch`, so staging two pops into
+    /// the same batch returns the same value twice; use a [`crate::Transaction`]
+    /// for that.
+    pub fn stage_pop<S: IterableStorage>(
+        &self,
+        storage: &S,
+        batch: &mut WriteBatch,
+        order: Order,
+    ) -> Result<Option<(K, V)>, StorageError<Enc>> {
+        let Some((priority, sequence, value)) = self.peek_entry(storage, order)? else {
+            return Ok(None);
+        };
+
+        let values = self.values().at(priority.clone())?;
+        values.at(sequence)?.stage_delete(batch)?;
+
+        // Reset the sequence counter once a priority is drained, i.e. the popped
+        // value was its only value.
+        let drained = values
+            .range(
+                storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )?
+            .nth(1)
+            .transpose()?
+            .is_none();
+        if drained {
+            self.stage_counter(batch, self.counter_key(&priority)?, 0)?;
+        }
+
+        let len = self.len(storage)?;
+        self.stage_counter(batch, self.namespace(LEN_NAMESPACE), len.saturating_sub(1))?;
+
+        Ok(Some((priority, value)))
+    }
+
+    /// Removes and returns all values at the given priority, in the order they
+    /// were pushed.
+    pub fn pop_all_at<S: StorageMut + IterableStorage>(
+        &self,
+        storage: &mut S,
+        priority: K,
+    ) -> Result<Vec<V>, StorageError<Enc>> {
+        let values = self.values().at(priority.clone())?;
+        let entries = values
+            .range(
+                storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut batch = WriteBatch::new();
+        let mut popped = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            values.at(key.0)?.stage_delete(&mut batch)?;
+            popped.push(value);
         }
+        self.stage_counter(&mut batch, self.counter_key(&priority)?, 0)?;
+
+        let len = self.len(storage)?;
+        let len = len.saturating_sub(popped.len() as u64);
+        self.stage_counter(&mut batch, self.namespace(LEN_NAMESPACE), len)?;
+        storage.write_batch(batch)?;
+
+        Ok(popped)
     }
-} */
+
+    /// Removes every value and counter of the queue with a single range delete.
+    pub fn clear<S: StorageMut>(&self, storage: &mut S) -> Result<(), StorageError<Enc>> {
+        let (low, high) = prefix_range(&self.namespace.prefix());
+        Ok(storage.delete_range_raw(low, high)?)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -202,32 +456,184 @@ mod tests {
         assert_eq!(pq.pop(&mut storage, Order::Ascending).unwrap(), None);
     }
 
-    // #[test]
-    // fn test_multi_priority_queue() {
-    //     let mut storage = BTreeMap::new();
-    //     let mpq: MultiPriorityQueue<i32, String, DisplayEncoding> =
-    //         MultiPriorityQueue::new(b"test_mpq");
+    #[test]
+    fn test_multi_priority_queue() {
+        let mut storage = BTreeMap::new();
+        let mpq: MultiPriorityQueue<i32, String, DisplayEncoding> =
+            MultiPriorityQueue::new(b"test_mpq");
+
+        // Test pushing elements
+        mpq.push(&mut storage, 1, &"first-1".to_string()).unwrap();
+        mpq.push(&mut storage, 2, &"second-1".to_string()).unwrap();
+        mpq.push(&mut storage, 1, &"first-2".to_string()).unwrap();
+        mpq.push(&mut storage, 2, &"second-2".to_string()).unwrap();
+        assert_eq!(mpq.len(&storage).unwrap(), 4);
+
+        // Test peeking
+        assert_eq!(
+            mpq.peek(&storage, Order::Ascending).unwrap(),
+            Some((1, "first-1".to_string()))
+        );
+        assert_eq!(
+            mpq.peek(&storage, Order::Descending).unwrap(),
+            Some((2, "second-1".to_string()))
+        );
+
+        // Test popping, FIFO within a priority
+        assert_eq!(
+            mpq.pop(&mut storage, Order::Ascending).unwrap(),
+            Some((1, "first-1".to_string()))
+        );
+        assert_eq!(
+            mpq.pop(&mut storage, Order::Descending).unwrap(),
+            Some((2, "second-1".to_string()))
+        );
+        assert_eq!(
+            mpq.pop(&mut storage, Order::Descending).unwrap(),
+            Some((2, "second-2".to_string()))
+        );
+        assert_eq!(mpq.len(&storage).unwrap(), 1);
+        assert_eq!(
+            mpq.pop(&mut storage, Order::Descending).unwrap(),
+            Some((1, "first-2".to_string()))
+        );
+        assert_eq!(mpq.pop(&mut storage, Order::Ascending).unwrap(), None);
+        assert!(mpq.is_empty(&storage).unwrap());
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn test_multi_priority_queue_pop_all_at() {
+        let mut storage = BTreeMap::new();
+        let mpq: MultiPriorityQueue<u8, String, DisplayEncoding> =
+            MultiPriorityQueue::new(b"test_mpq");
+
+        for i in 0..3 {
+            mpq.push(&mut storage, 1, &format!("first-{i}")).unwrap();
+            mpq.push(&mut storage, 2, &format!("second-{i}")).unwrap();
+        }
+
+        assert_eq!(
+            mpq.pop_all_at(&mut storage, 2).unwrap(),
+            vec!["second-0", "second-1", "second-2"]
+        );
+        assert_eq!(
+            mpq.pop_all_at(&mut storage, 2).unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(mpq.len(&storage).unwrap(), 3);
+
+        // Sequence numbers keep increasing while a priority is non-empty
+        mpq.pop(&mut storage, Order::Ascending).unwrap();
+        mpq.push(&mut storage, 1, &"first-3".to_string()).unwrap();
+        assert_eq!(
+            mpq.pop_all_at(&mut storage, 1).unwrap(),
+            vec!["first-1", "first-2", "first-3"]
+        );
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn test_multi_priority_queue_isolation() {
+        let mut storage = BTreeMap::new();
+        let a: MultiPriorityQueue<u8, String, DisplayEncoding> = MultiPriorityQueue::new(b"a");
+        let b: MultiPriorityQueue<u8, String, DisplayEncoding> = MultiPriorityQueue::new(b"b");
 
-    //     // Test pushing elements
-    //     mpq.push(&mut storage, 1, &"first-1".to_string()).unwrap();
-    //     mpq.push(&mut storage, 1, &"first-2".to_string()).unwrap();
-    //     mpq.push(&mut storage, 2, &"second-1".to_string()).unwrap();
-    //     mpq.push(&mut storage, 2, &"second-2".to_string()).unwrap();
+        a.push(&mut storage, 1, &"a-1".to_string()).unwrap();
+        b.push(&mut storage, 1, &"b-1".to_string()).unwrap();
+        a.push(&mut storage, 1, &"a-2".to_string()).unwrap();
 
-    //     // Test peeking
-    //     let (priority, values) = mpq.peek(&storage, true).unwrap().unwrap();
-    //     assert_eq!(priority, 1);
-    //     assert_eq!(values, vec!["first-1".to_string(), "first-2".to_string()]);
+        assert_eq!(a.len(&storage).unwrap(), 2);
+        assert_eq!(b.len(&storage).unwrap(), 1);
+        assert_eq!(b.pop_all_at(&mut storage, 1).unwrap(), vec!["b-1"]);
+        assert_eq!(a.pop_all_at(&mut storage, 1).unwrap(), vec!["a-1", "a-2"]);
+    }
 
-    //     // Test popping
-    //     let (priority, values) = mpq.pop(&mut storage, true).unwrap().unwrap();
-    //     assert_eq!(priority, 1);
-    //     assert_eq!(values, vec!["first-1".to_string(), "first-2".to_string()]);
+    #[test]
+    fn test_priority_queue_with_neighbours() {
+        let mut storage = BTreeMap::new();
+        let before: PriorityQueue<i32, String, DisplayEncoding> = PriorityQueue::new(b"a");
+        let pq: PriorityQueue<i32, String, DisplayEncoding> = PriorityQueue::new(b"b");
+        let after: PriorityQueue<i32, String, DisplayEncoding> = PriorityQueue::new(b"c");
 
-    //     let (priority, values) = mpq.pop(&mut storage, false).unwrap().unwrap();
-    //     assert_eq!(priority, 2);
-    //     assert_eq!(values, vec!["second-1".to_string(), "second-2".to_string()]);
+        before
+            .push(&mut storage, 100, &"before".to_string())
+            .unwrap();
+        after
+            .push(&mut storage, -100, &"after".to_string())
+            .unwrap();
+        pq.push(&mut storage, 1, &"first".to_string()).unwrap();
+        pq.push(&mut storage, 2, &"second".to_string()).unwrap();
 
-    //     assert_eq!(mpq.pop(&mut storage, true).unwrap(), None);
-    // }
+        assert_eq!(
+            pq.peek(&storage, Order::Ascending).unwrap(),
+            Some((1, "first".to_string()))
+        );
+        assert_eq!(
+            pq.peek(&storage, Order::Descending).unwrap(),
+            Some((2, "second".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_stage_pop() {
+        let mut storage = BTreeMap::new();
+        let pq: PriorityQueue<i32, String, DisplayEncoding> = PriorityQueue::new(b"pq");
+        let mpq: MultiPriorityQueue<i32, String, DisplayEncoding> = MultiPriorityQueue::new(b"mpq");
+        let popped: Item<u32, DisplayEncoding> = Item::new(b"popped");
+        pq.push(&mut storage, 1, &"pq".to_string()).unwrap();
+        mpq.push(&mut storage, 1, &"mpq".to_string()).unwrap();
+
+        // Move one value out of each queue, and count the moves, in one batch
+        let mut batch = WriteBatch::new();
+        let a = pq
+            .stage_pop(&storage, &mut batch, Order::Ascending)
+            .unwrap();
+        let b = mpq
+            .stage_pop(&storage, &mut batch, Order::Ascending)
+            .unwrap();
+        popped.stage_save(&mut batch, &2).unwrap();
+        assert_eq!(a, Some((1, "pq".to_string())));
+        assert_eq!(b, Some((1, "mpq".to_string())));
+        assert_eq!(pq.peek(&storage, Order::Ascending).unwrap(), a);
+
+        storage.write_batch(batch).unwrap();
+        assert_eq!(pq.peek(&storage, Order::Ascending).unwrap(), None);
+        assert_eq!(mpq.len(&storage).unwrap(), 0);
+        assert_eq!(popped.may_load(&storage).unwrap(), Some(2));
+        // Only the popped count remains: the queues leave no counters behind
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut storage = BTreeMap::new();
+        let before: PriorityQueue<i32, String, DisplayEncoding> = PriorityQueue::new(b"a");
+        let pq: PriorityQueue<i32, String, DisplayEncoding> = PriorityQueue::new(b"b");
+        let mpq: MultiPriorityQueue<i32, String, DisplayEncoding> = MultiPriorityQueue::new(b"c");
+        before.push(&mut storage, 0, &"before".to_string()).unwrap();
+        for i in 0..3 {
+            pq.push(&mut storage, i, &i.to_string()).unwrap();
+            mpq.push(&mut storage, i, &i.to_string()).unwrap();
+        }
+
+        pq.remove_prefix(&mut storage, 1).unwrap();
+        assert_eq!(
+            pq.pop(&mut storage, Order::Descending).unwrap(),
+            Some((2, "2".to_string()))
+        );
+        pq.clear(&mut storage).unwrap();
+        assert_eq!(pq.peek(&storage, Order::Ascending).unwrap(), None);
+
+        mpq.clear(&mut storage).unwrap();
+        assert_eq!(mpq.len(&storage).unwrap(), 0);
+        assert_eq!(mpq.peek(&storage, Order::Ascending).unwrap(), None);
+
+        // Only the neighbouring queue remains
+        assert_eq!(storage.len(), 1);
+        assert_eq!(
+            before.peek(&storage, Order::Ascending).unwrap(),
+            Some((0, "before".to_string()))
+        );
+    }
 }
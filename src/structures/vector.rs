@@ -1,18 +1,31 @@
-#![allow(unused)]
-
-use std::ops::Bound;
+use std::{borrow::Cow, ops::Bound};
 
 use crate::{
-    DataStructure, DsIter, Encodable, Item, IterableStorage, KeyEncoding, KeySerializeError, Map,
-    NonTerminal, Order, Storage, StorageError,
+    Codec, DataStructure, DsIter, Encodable, Encoding, Item, IterableStorage, KeyEncoding,
+    KeySerializeError, KeyType, Map, NonTerminal, Order, Storage, StorageError, StorageMut,
+    WriteBatch,
 };
 
-// TODO: Vector needs some sort of pre-save/delete hook to update the counter.
+/// Index at which the length counter is stored. Elements can never live at this
+/// index, since the length of the vector is at most `usize::MAX`.
+const COUNTER_INDEX: usize = usize::MAX;
 
+/// A list of data structures indexed by `usize`, alongside an [`Item`] counting
+/// them.
+///
+/// Vector has keys
+///      prefix/0 -> value
+///      ...
+///      prefix/n-1 -> value
+///      prefix/usize::MAX -> counter
+///
+/// The counter is serialized using the `usize` key encoding, and sorts after all
+/// elements so that it never interleaves with them during iteration. Vectors of
+/// [`Item`]s store values directly, with [`Vector::push`], [`Vector::pop`] and
+/// friends. Vectors of other structures hand out their elements with
+/// [`Vector::at`].
 pub struct Vector<'a, V: DataStructure> {
     map: Map<'a, usize, V>,
-    /// Counter serialized using usize Key encoding.
-    counter: Item<'a, usize, KeyEncoding>,
 }
 
 impl<'a, V: DataStructure> DataStructure for Vector<'a, V> {
@@ -23,45 +36,131 @@ impl<'a, V: DataStructure> DataStructure for Vector<'a, V> {
 
     fn with_prefix(prefix: Vec<u8>) -> Self {
         Self {
-            map: Map::with_prefix(prefix.clone()),
-            counter: Item::with_prefix(prefix),
+            map: Map::with_prefix(prefix),
         }
     }
 
     fn should_skip_key(key: &Self::Key) -> bool {
-        // Vector has keys
-        //      prefix -> counter
-        //      prefix/0 -> value
-        //      ...
-        //      prefix/n -> value
-        // We skip the counter key, which is when the second element is None.
-        key.1.as_ref().map_or(true, V::should_skip_key)
+        // We skip the counter key, and the keys the elements skip themselves.
+        key.0 == COUNTER_INDEX || key.1.as_ref().is_some_and(V::should_skip_key)
     }
 }
 
 impl<V: DataStructure> Vector<'static, V> {
+    /// Creates a vector in the length-delimited namespace `key`.
     pub const fn new(key: &'static [u8]) -> Self {
+        Self { map: Map::new(key) }
+    }
+
+    /// Creates a vector whose keys are prefixed by `key` verbatim. See
+    /// [`Map::new_raw`].
+    pub const fn new_raw(key: &'static [u8]) -> Self {
         Self {
-            map: Map::new(key),
-            counter: Item::new(key),
+            map: Map::new_raw(key),
+        }
+    }
+}
+
+/// Re-types an error of the counter, whose value is key-encoded.
+fn counter_error<Enc: Encoding>(error: StorageError<KeyEncoding>) -> StorageError<Enc> {
+    match error {
+        StorageError::KeySerialize(e) | StorageError::ValueSerialize(e) => {
+            StorageError::KeySerialize(e)
+        }
+        StorageError::KeyDeserialize(e) | StorageError::ValueDeserialize(e) => {
+            StorageError::KeyDeserialize(e)
         }
+        StorageError::IndexOutOfBounds(index, len) => StorageError::IndexOutOfBounds(index, len),
+        StorageError::MissingElement(index, len) => StorageError::MissingElement(index, len),
+        StorageError::Backend(e) => StorageError::Backend(e),
     }
 }
 
 impl<'a, V: DataStructure> Vector<'a, V> {
-    fn key(&self, key: &usize) -> Result<Vec<u8>, KeySerializeError> {
-        let encoded = Encodable::<KeyEncoding>::encode(key)?;
-        let full = [self.map.prefix(), &encoded].concat();
+    fn key(&self, index: usize) -> Result<Vec<u8>, KeySerializeError> {
+        let encoded = Encodable::<KeyEncoding>::encode(&index)?;
+        let full = [self.map.prefix().as_ref(), &encoded].concat();
 
         Ok(full)
     }
 
-    pub fn len<S: Storage>(&self, storage: &S) -> Result<usize, StorageError<KeyEncoding>> {
-        self.counter.may_load(storage).map(|v| v.unwrap_or(0))
+    /// The length counter, serialized using usize Key encoding.
+    fn counter(&self) -> Result<Item<'a, usize, KeyEncoding>, KeySerializeError> {
+        Ok(Item::with_prefix(self.key(COUNTER_INDEX)?))
+    }
+
+    fn stage_len(&self, batch: &mut WriteBatch, len: usize) -> Result<(), StorageError<V::Enc>> {
+        let counter = self.counter()?;
+        let staged = if len == 0 {
+            counter.stage_delete(batch)
+        } else {
+            counter.stage_save(batch, &len)
+        };
+        staged.map_err(counter_error)
+    }
+
+    pub fn prefix(&self) -> Cow<'_, [u8]> {
+        self.map.prefix()
+    }
+
+    pub fn len<S: Storage>(&self, storage: &S) -> Result<usize, StorageError<V::Enc>> {
+        let len = self.counter()?.may_load(storage).map_err(counter_error)?;
+        Ok(len.unwrap_or(0))
     }
 
+    pub fn is_empty<S: Storage>(&self, storage: &S) -> Result<bool, StorageError<V::Enc>> {
+        self.len(storage).map(|len| len == 0)
+    }
+
+    /// Returns the element at `index`. The vector's length is neither checked nor
+    /// updated, so elements must be added with [`Vector::push`] or
+    /// [`Vector::push_with`].
     pub fn at(&self, index: usize) -> Result<V, KeySerializeError> {
-        Ok(V::with_prefix(self.key(&index)?))
+        self.map.at(index)
+    }
+
+    /// Appends an element to the end of the vector, filled in by `fill`, and
+    /// returns its index. The counter is only updated if `fill` succeeds.
+    pub fn push_with<S: StorageMut>(
+        &self,
+        storage: &mut S,
+        fill: impl FnOnce(&mut S, V) -> Result<(), StorageError<V::Enc>>,
+    ) -> Result<usize, StorageError<V::Enc>> {
+        let index = self.len(storage)?;
+        if index == COUNTER_INDEX {
+            return Err(StorageError::IndexOutOfBounds(index, index));
+        }
+        fill(storage, self.at(index)?)?;
+        let mut batch = WriteBatch::new();
+        self.stage_len(&mut batch, index + 1)?;
+        storage.write_batch(batch)?;
+        Ok(index)
+    }
+
+    /// Shortens the vector to `len` elements, deleting the rest along with
+    /// everything nested in them. Has no effect if `len` is greater than or equal
+    /// to the current length.
+    pub fn truncate<S: StorageMut>(
+        &self,
+        storage: &mut S,
+        len: usize,
+    ) -> Result<(), StorageError<V::Enc>> {
+        let old_len = self.len(storage)?;
+        if len >= old_len {
+            return Ok(());
+        }
+        let mut batch = WriteBatch::new();
+        batch.delete_range(
+            Bound::Included(self.key(len)?),
+            Bound::Excluded(self.key(old_len)?),
+        );
+        self.stage_len(&mut batch, len)?;
+        Ok(storage.write_batch(batch)?)
+    }
+
+    /// Removes all elements from the vector.
+    pub fn clear<S: StorageMut>(&self, storage: &mut S) -> Result<(), StorageError<V::Enc>> {
+        self.truncate(storage, 0)
     }
 
     pub fn range<'b, S: IterableStorage>(
@@ -70,25 +169,138 @@ impl<'a, V: DataStructure> Vector<'a, V> {
         start: Bound<usize>,
         end: Bound<usize>,
         order: Order,
-    ) -> Result<DsIter<'b, Self>, KeySerializeError> {
+    ) -> Result<DsIter<'b, Self, S::Iter<'b>>, StorageError<V::Enc>> {
         let start = match start {
-            Bound::Included(k) => Bound::Included(self.key(&k)?),
-            Bound::Excluded(k) => Bound::Excluded(self.key(&k)?),
-            Bound::Unbounded => Bound::Unbounded,
+            Bound::Included(k) => Bound::Included(KeyType::Raw(self.key(k)?)),
+            Bound::Excluded(k) => Bound::Excluded(KeyType::Raw(self.key(k)?)),
+            Bound::Unbounded => Bound::Included(KeyType::Raw(self.key(0)?)),
         };
         let end = match end {
-            Bound::Included(k) => Bound::Included(self.key(&k)?),
-            Bound::Excluded(k) => Bound::Excluded(self.key(&k)?),
-            Bound::Unbounded => Bound::Unbounded,
+            Bound::Included(k) => {
+                Bound::Included(KeyType::Raw(self.key(k.min(COUNTER_INDEX - 1))?))
+            }
+            Bound::Excluded(k) => Bound::Excluded(KeyType::Raw(self.key(k)?)),
+            Bound::Unbounded => Bound::Excluded(KeyType::Raw(self.key(COUNTER_INDEX)?)),
         };
-        let iter = storage.iter(start, end, order)?;
+        let iter = storage.iter::<KeyType<usize>>(start, end, order)?;
+        Ok(DsIter::new(self.map.prefix().to_vec(), iter))
+    }
+
+    pub fn iter<'b, S: IterableStorage>(
+        &self,
+        storage: &'b S,
+        order: Order,
+    ) -> Result<DsIter<'b, Self, S::Iter<'b>>, StorageError<V::Enc>> {
+        // The counter key is under the same prefix, and skipped while decoding
+        let iter = storage.prefix_iter(&self.map.prefix(), order)?;
         Ok(DsIter::new(self.map.prefix().to_vec(), iter))
     }
 }
 
+impl<'a, T: Codec<Enc>, Enc: Encoding> Vector<'a, Item<'a, T, Enc>> {
+    /// Loads the value at `index`, which is below `len`. Fails if it is missing.
+    fn load<S: Storage>(
+        &self,
+        storage: &S,
+        index: usize,
+        len: usize,
+    ) -> Result<T, StorageError<Enc>> {
+        self.at(index)?
+            .may_load(storage)?
+            .ok_or(StorageError::MissingElement(index, len))
+    }
+
+    /// Returns the value at `index`, or `None` if `index` is out of bounds.
+    pub fn get<S: Storage>(
+        &self,
+        storage: &S,
+        index: usize,
+    ) -> Result<Option<T>, StorageError<Enc>> {
+        if index >= self.len(storage)? {
+            return Ok(None);
+        }
+        self.at(index)?.may_load(storage)
+    }
+
+    /// Overwrites the value at `index`. Fails if `index` is out of bounds.
+    pub fn set<S: StorageMut>(
+        &self,
+        storage: &mut S,
+        index: usize,
+        value: &T,
+    ) -> Result<(), StorageError<Enc>> {
+        let len = self.len(storage)?;
+        if index >= len {
+            return Err(StorageError::IndexOutOfBounds(index, len));
+        }
+        self.at(index)?.save(storage, value)
+    }
+
+    /// Appends a value to the end of the vector, returning its index.
+    pub fn push<S: StorageMut>(
+        &self,
+        storage: &mut S,
+        value: &T,
+    ) -> Result<usize, StorageError<Enc>> {
+        let index = self.len(storage)?;
+        if index == COUNTER_INDEX {
+            return Err(StorageError::IndexOutOfBounds(index, index));
+        }
+        let mut batch = WriteBatch::new();
+        self.at(index)?.stage_save(&mut batch, value)?;
+        self.stage_len(&mut batch, index + 1)?;
+        storage.write_batch(batch)?;
+        Ok(index)
+    }
+
+    /// Removes the last value and returns it, or `None` if the vector is empty.
+    /// Fails, without writing, if the last value is missing.
+    pub fn pop<S: StorageMut>(&self, storage: &mut S) -> Result<Option<T>, StorageError<Enc>> {
+        let len = self.len(storage)?;
+        let Some(index) = len.checked_sub(1) else {
+            return Ok(None);
+        };
+        let value = self.load(storage, index, len)?;
+        let mut batch = WriteBatch::new();
+        self.at(index)?.stage_delete(&mut batch)?;
+        self.stage_len(&mut batch, index)?;
+        storage.write_batch(batch)?;
+        Ok(Some(value))
+    }
+
+    /// Removes the value at `index` and returns it, replacing it with the last value.
+    /// Fails if `index` is out of bounds, and fails without writing if either
+    /// value is missing.
+    pub fn swap_remove<S: StorageMut>(
+        &self,
+        storage: &mut S,
+        index: usize,
+    ) -> Result<T, StorageError<Enc>> {
+        let len = self.len(storage)?;
+        if index >= len {
+            return Err(StorageError::IndexOutOfBounds(index, len));
+        }
+        let last_index = len - 1;
+        let last_value = self.load(storage, last_index, len)?;
+        let mut batch = WriteBatch::new();
+        self.at(last_index)?.stage_delete(&mut batch)?;
+        self.stage_len(&mut batch, last_index)?;
+
+        if index == last_index {
+            storage.write_batch(batch)?;
+            return Ok(last_value);
+        }
+
+        let value = self.load(storage, index, len)?;
+        self.at(index)?.stage_save(&mut batch, &last_value)?;
+        storage.write_batch(batch)?;
+        Ok(value)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
     use crate::mock::DisplayEncoding;
 
@@ -100,18 +312,202 @@ mod test {
 
         let mut storage: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
         assert_eq!(VECTOR.len(&storage), Ok(0));
+        assert_eq!(VECTOR.pop(&mut storage), Ok(None));
 
-        VECTOR
-            .at(0)
-            .unwrap()
-            .save(&mut storage, &"bar".to_string())
-            .unwrap();
+        assert_eq!(VECTOR.push(&mut storage, &"bar".to_string()), Ok(0));
+        assert_eq!(VECTOR.push(&mut storage, &"baz".to_string()), Ok(1));
+        assert_eq!(VECTOR.len(&storage), Ok(2));
+        assert_eq!(VECTOR.get(&storage, 0), Ok(Some("bar".to_string())));
+        assert_eq!(VECTOR.get(&storage, 2), Ok(None));
 
+        VECTOR.set(&mut storage, 1, &"qux".to_string()).unwrap();
+        assert_eq!(VECTOR.get(&storage, 1), Ok(Some("qux".to_string())));
         assert_eq!(
-            VECTOR.at(0).unwrap().may_load(&storage),
-            Ok(Some("bar".to_string()))
+            VECTOR.set(&mut storage, 2, &"quux".to_string()),
+            Err(StorageError::IndexOutOfBounds(2, 2))
         );
-        // TODO: Vector push doesn't increment the counter.
+
+        assert_eq!(VECTOR.pop(&mut storage), Ok(Some("qux".to_string())));
         assert_eq!(VECTOR.len(&storage), Ok(1));
+        assert_eq!(VECTOR.pop(&mut storage), Ok(Some("bar".to_string())));
+        assert_eq!(VECTOR.len(&storage), Ok(0));
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn test_vector_truncate_swap_remove() {
+        const VECTOR: Vector<Item<usize, DisplayEncoding>> = Vector::new(b"foo");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for i in 0..5 {
+            VECTOR.push(&mut storage, &i).unwrap();
+        }
+
+        assert_eq!(VECTOR.swap_remove(&mut storage, 1), Ok(1));
+        assert_eq!(VECTOR.swap_remove(&mut storage, 3), Ok(3));
+        assert_eq!(
+            VECTOR.swap_remove(&mut storage, 3),
+            Err(StorageError::IndexOutOfBounds(3, 3))
+        );
+        let values: Vec<_> = VECTOR
+            .iter(&storage, Order::Ascending)
+            .unwrap()
+            .map(|res| res.map(|((index, _), value)| (index, value)).unwrap())
+            .collect();
+        assert_eq!(values, vec![(0, 0), (1, 4), (2, 2)]);
+
+        VECTOR.truncate(&mut storage, 1).unwrap();
+        assert_eq!(VECTOR.len(&storage), Ok(1));
+        assert_eq!(VECTOR.get(&storage, 1), Ok(None));
+
+        VECTOR.clear(&mut storage).unwrap();
+        assert_eq!(VECTOR.len(&storage), Ok(0));
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn test_vector_missing_element() {
+        const VECTOR: Vector<Item<usize, DisplayEncoding>> = Vector::new(b"foo");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for i in 0..3 {
+            VECTOR.push(&mut storage, &i).unwrap();
+        }
+        // Delete values behind the vector's back.
+        VECTOR.at(0).unwrap().delete(&mut storage).unwrap();
+        VECTOR.at(2).unwrap().delete(&mut storage).unwrap();
+        let before = storage.clone();
+
+        assert_eq!(
+            VECTOR.pop(&mut storage),
+            Err(StorageError::MissingElement(2, 3))
+        );
+        assert_eq!(
+            VECTOR.swap_remove(&mut storage, 1),
+            Err(StorageError::MissingElement(2, 3))
+        );
+        assert_eq!(storage, before);
+
+        VECTOR.set(&mut storage, 2, &2).unwrap();
+        assert_eq!(
+            VECTOR.swap_remove(&mut storage, 0),
+            Err(StorageError::MissingElement(0, 3))
+        );
+        assert_eq!(VECTOR.len(&storage), Ok(3));
+        assert_eq!(VECTOR.pop(&mut storage), Ok(Some(2)));
+    }
+
+    #[test]
+    fn test_vector_iter() {
+        const VECTOR: Vector<Item<usize, DisplayEncoding>> = Vector::new(b"foo");
+        const OTHER: Vector<Item<usize, DisplayEncoding>> = Vector::new(b"bar");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for i in 0..10 {
+            VECTOR.push(&mut storage, &(i * 10)).unwrap();
+            OTHER.push(&mut storage, &i).unwrap();
+        }
+
+        let values: Vec<_> = VECTOR
+            .iter(&storage, Order::Descending)
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(values, (0..10).rev().map(|i| i * 10).collect::<Vec<_>>());
+
+        let values: Vec<_> = VECTOR
+            .range(
+                &storage,
+                Bound::Excluded(2),
+                Bound::Included(4),
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| res.map(|((index, _), value)| (index, value)).unwrap())
+            .collect();
+        assert_eq!(values, vec![(3, 30), (4, 40)]);
+    }
+
+    #[test]
+    fn test_nested_vector() {
+        const MAP: Map<String, Vector<Item<usize, DisplayEncoding>>> = Map::new(b"foo");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let vector = MAP.at("bar").unwrap();
+        vector.push(&mut storage, &1).unwrap();
+        vector.push(&mut storage, &2).unwrap();
+
+        // The counter is skipped when iterating over the outer map.
+        let values: Vec<_> = MAP
+            .range(
+                &storage,
+                Bound::Unbounded,
+                Bound::Unbounded,
+                Order::Ascending,
+            )
+            .unwrap()
+            .map(|res| {
+                let ((outer, inner), value) = res.unwrap();
+                (outer, inner.map(|(index, _)| index), value)
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("bar".to_string(), Some(0), 1),
+                ("bar".to_string(), Some(1), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vector_of_maps() {
+        const VECTOR: Vector<Map<String, Item<usize, DisplayEncoding>>> = Vector::new(b"foo");
+
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for i in 0..3 {
+            let index = VECTOR
+                .push_with(&mut storage, |storage, map| {
+                    map.at("a")?.save(storage, &i)?;
+                    map.at("b")?.save(storage, &(i * 10))
+                })
+                .unwrap();
+            assert_eq!(index, i);
+        }
+        assert_eq!(VECTOR.len(&storage), Ok(3));
+        assert_eq!(
+            VECTOR.at(1).unwrap().at("b").unwrap().may_load(&storage),
+            Ok(Some(10))
+        );
+
+        // A failed fill leaves the length as it was.
+        let failed = VECTOR.push_with(&mut storage, |_, _| {
+            Err(StorageError::IndexOutOfBounds(0, 0))
+        });
+        assert_eq!(failed, Err(StorageError::IndexOutOfBounds(0, 0)));
+        assert_eq!(VECTOR.len(&storage), Ok(3));
+
+        // Truncating deletes the nested entries, and the counter is skipped.
+        VECTOR.truncate(&mut storage, 2).unwrap();
+        let values: Vec<_> = VECTOR
+            .iter(&storage, Order::Descending)
+            .unwrap()
+            .map(|res| {
+                let ((index, key), value) = res.unwrap();
+                (index, key.unwrap().0, value)
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (1, "b".to_string(), 10),
+                (1, "a".to_string(), 1),
+                (0, "b".to_string(), 0),
+                (0, "a".to_string(), 0),
+            ]
+        );
+
+        VECTOR.clear(&mut storage).unwrap();
+        assert!(storage.is_empty());
     }
 }
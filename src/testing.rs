@@ -0,0 +1,683 @@
+//! Conformance tests for storage backends.
+//!
+//! Backend implementors can check their [`Storage`], [`StorageMut`] and
+//! [`IterableStorage`] implementations against the semantics libkv relies on, by
+//! invoking [`storage_conformance_tests!`](crate::storage_conformance_tests) with
+//! a constructor for an empty backend:
+//!
+//! ```ignore
+//! mod conformance {
+//!     libkv::storage_conformance_tests!(MyStorage::new);
+//! }
+//! ```
+//!
+//! Backends that borrow from something which must outlive them, such as a
+//! transaction over a temporary database, are tested through a [`Fixture`]
+//! owning both.
+//!
+//! Each check is also exposed as a function taking that constructor, for use in
+//! custom test harnesses. Every check must receive a fresh, empty backend on
+//! every call of the constructor.
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    ops::{Bound, RangeBounds},
+};
+
+use proptest::{collection::vec, prelude::*, test_runner::TestRunner};
+
+use crate::{
+    BackendError, Encodable, Item, IterableStorage, KeyEncoding, KeyType, KvPair, Map,
+    MultiPriorityQueue, Order, PriorityQueue, RawStorageError, Storage, StorageMut, Transaction,
+    Vector, WriteBatch,
+};
+
+/// Generates a `#[test]` for every conformance check, run against the backend or
+/// [`Fixture`](crate::testing::Fixture) returned by `$new`, e.g. `MyStorage::new`
+/// or `|| MyStorage::open(path)`.
+#[macro_export]
+macro_rules! storage_conformance_tests {
+    ($new:expr) => {
+        #[test]
+        fn conformance_get_set_delete() {
+            $crate::testing::get_set_delete($new);
+        }
+
+        #[test]
+        fn conformance_bounds() {
+            $crate::testing::bounds($new);
+        }
+
+        #[test]
+        fn conformance_keys_match_iter() {
+            $crate::testing::keys_match_iter($new);
+        }
+
+        #[test]
+        fn conformance_prefix_iter() {
+            $crate::testing::prefix_iter($new);
+        }
+
+        #[test]
+        fn conformance_write_batch() {
+            $crate::testing::write_batch($new);
+        }
+
+        #[test]
+        fn conformance_structures() {
+            $crate::testing::structures($new);
+        }
+
+        #[test]
+        fn conformance_transaction() {
+            $crate::testing::transaction($new);
+        }
+
+        #[test]
+        fn conformance_matches_reference() {
+            $crate::testing::matches_reference($new);
+        }
+    };
+}
+
+/// Owns a backend under test, along with anything that must outlive it. Every
+/// backend is a fixture of itself.
+pub trait Fixture {
+    type Storage<'a>: StorageMut + IterableStorage
+    where
+        Self: 'a;
+
+    /// Returns the backend. Each check calls this once per fixture.
+    fn storage(&mut self) -> Self::Storage<'_>;
+}
+
+impl<S: StorageMut + IterableStorage> Fixture for S {
+    type Storage<'a>
+        = Borrowed<'a, S>
+    where
+        Self: 'a;
+
+    fn storage(&mut self) -> Borrowed<'_, S> {
+        Borrowed(self)
+    }
+}
+
+/// A backend lent out by the [`Fixture`] owning it.
+pub struct Borrowed<'a, S>(pub &'a mut S);
+
+impl<S: Storage> Storage for Borrowed<'_, S> {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        self.0.get_raw(key)
+    }
+}
+
+impl<S: StorageMut> StorageMut for Borrowed<'_, S> {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.0.set_raw(key, value)
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.0.delete_raw(key)
+    }
+
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        self.0.delete_range_raw(low, high)
+    }
+
+    fn write_batch(&mut self, batch: WriteBatch) -> Result<(), BackendError> {
+        self.0.write_batch(batch)
+    }
+}
+
+impl<S: IterableStorage> IterableStorage for Borrowed<'_, S> {
+    type Keys<'a>
+        = S::Keys<'a>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = S::Iter<'a>
+    where
+        Self: 'a;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        self.0.keys(low, high, order)
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        self.0.iter(low, high, order)
+    }
+
+    fn prefix_keys(&self, prefix: &[u8], order: Order) -> Result<Self::Keys<'_>, BackendError> {
+        self.0.prefix_keys(prefix, order)
+    }
+
+    fn prefix_iter(&self, prefix: &[u8], order: Order) -> Result<Self::Iter<'_>, BackendError> {
+        self.0.prefix_iter(prefix, order)
+    }
+}
+
+/// Keys exercising the edges of the byte ordering: the empty key, prefixes of
+/// other keys, and runs of `0x00` and `0xFF`.
+const KEYS: &[&[u8]] = &[
+    b"",
+    b"\x00",
+    b"\x00\x00",
+    b"\x00\x01",
+    b"\x01",
+    b"a",
+    b"ab",
+    b"b",
+    b"\xfe\xff",
+    b"\xff",
+    b"\xff\xff",
+];
+
+fn raw_bound(bound: &Bound<Vec<u8>>) -> Bound<KeyType<()>> {
+    bound.clone().map(KeyType::Raw)
+}
+
+fn all_bounds(keys: &[&[u8]]) -> Vec<Bound<Vec<u8>>> {
+    let mut bounds = vec![Bound::Unbounded];
+    for key in keys {
+        bounds.push(Bound::Included(key.to_vec()));
+        bounds.push(Bound::Excluded(key.to_vec()));
+    }
+    bounds
+}
+
+/// Entries of `reference` within `(low, high)`, in `order`.
+fn expected_range(
+    reference: &BTreeMap<Vec<u8>, Vec<u8>>,
+    low: &Bound<Vec<u8>>,
+    high: &Bound<Vec<u8>>,
+    order: Order,
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let range = (low.clone(), high.clone());
+    let mut entries: Vec<_> = reference
+        .iter()
+        .filter(|(key, _)| range.contains(*key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if order == Order::Descending {
+        entries.reverse();
+    }
+    entries
+}
+
+fn get<S: Storage>(storage: &S, key: &[u8]) -> Option<Vec<u8>> {
+    storage.get_raw(key).unwrap().map(Cow::into_owned)
+}
+
+fn owned_entry(entry: Result<KvPair<'_>, BackendError>) -> (Vec<u8>, Vec<u8>) {
+    let (key, value) = entry.unwrap();
+    (key.into_owned(), value.into_owned())
+}
+
+/// Checks single-key reads, writes and deletes.
+pub fn get_set_delete<F: Fixture>(new: impl Fn() -> F) {
+    let mut fixture = new();
+    let mut storage = fixture.storage();
+    for key in KEYS {
+        assert_eq!(get(&storage, key), None, "key {key:?} in empty storage");
+    }
+
+    for (i, key) in KEYS.iter().enumerate() {
+        storage.set_raw(key.to_vec(), vec![i as u8]).unwrap();
+    }
+    for (i, key) in KEYS.iter().enumerate() {
+        assert_eq!(get(&storage, key), Some(vec![i as u8]), "key {key:?}");
+    }
+
+    // Overwrites replace the value, and empty values are distinct from missing ones
+    storage.set_raw(b"a".to_vec(), vec![]).unwrap();
+    assert_eq!(get(&storage, b"a"), Some(vec![]));
+    storage.set_raw(b"a".to_vec(), b"value".to_vec()).unwrap();
+    assert_eq!(get(&storage, b"a"), Some(b"value".to_vec()));
+
+    storage.delete_raw(b"a").unwrap();
+    assert_eq!(get(&storage, b"a"), None);
+    assert_eq!(get(&storage, b"ab"), Some(vec![6]));
+    // Deleting a missing key is a no-op
+    storage.delete_raw(b"a").unwrap();
+    storage.delete_raw(b"missing").unwrap();
+    assert_eq!(get(&storage, b"a"), None);
+
+    // Typed keys are encoded with the key encoding
+    storage.set(&42u32, b"typed".to_vec()).unwrap();
+    assert_eq!(get(&storage, &42u32.to_be_bytes()), Some(b"typed".to_vec()));
+    let value = storage.get(&42u32).unwrap();
+    assert_eq!(value.as_deref(), Some(b"typed".as_slice()));
+    storage.delete(&42u32).unwrap();
+    assert_eq!(storage.get(&42u32).unwrap(), None);
+}
+
+/// Checks every combination of `Bound`s, including empty and inverted ranges,
+/// in both orders.
+pub fn bounds<F: Fixture>(new: impl Fn() -> F) {
+    let mut fixture = new();
+    let mut storage = fixture.storage();
+    let mut reference = BTreeMap::new();
+    // Leave every other key missing, so that bounds fall both on and between keys
+    for (i, key) in KEYS.iter().enumerate().filter(|(i, _)| i % 2 == 0) {
+        storage.set_raw(key.to_vec(), vec![i as u8]).unwrap();
+        reference.insert(key.to_vec(), vec![i as u8]);
+    }
+
+    let bounds = all_bounds(KEYS);
+    for low in &bounds {
+        for high in &bounds {
+            for order in [Order::Ascending, Order::Descending] {
+                let expected = expected_range(&reference, low, high, order);
+                let actual: Vec<_> = storage
+                    .iter(raw_bound(low), raw_bound(high), order)
+                    .unwrap()
+                    .map(owned_entry)
+                    .collect();
+                assert_eq!(actual, expected, "iter({low:?}, {high:?}, {order:?})");
+            }
+        }
+    }
+}
+
+/// Checks that `IterableStorage::keys` yields exactly the keys of
+/// `IterableStorage::iter`.
+pub fn keys_match_iter<F: Fixture>(new: impl Fn() -> F) {
+    let mut fixture = new();
+    let mut storage = fixture.storage();
+    for (i, key) in KEYS.iter().enumerate() {
+        storage.set_raw(key.to_vec(), vec![i as u8]).unwrap();
+    }
+
+    let bounds = all_bounds(&[b"", b"\x00\x00", b"ab", b"\xff"]);
+    for low in &bounds {
+        for high in &bounds {
+            for order in [Order::Ascending, Order::Descending] {
+                let keys: Vec<_> = storage
+                    .keys(raw_bound(low), raw_bound(high), order)
+                    .unwrap()
+                    .map(Result::unwrap)
+                    .collect();
+                let iter_keys: Vec<_> = storage
+                    .iter(raw_bound(low), raw_bound(high), order)
+                    .unwrap()
+                    .map(|entry| entry.unwrap().0)
+                    .collect();
+                assert_eq!(keys, iter_keys, "keys({low:?}, {high:?}, {order:?})");
+            }
+        }
+    }
+}
+
+/// Checks prefix scans, including the empty prefix and prefixes ending in `0xFF`,
+/// in both orders.
+pub fn prefix_iter<F: Fixture>(new: impl Fn() -> F) {
+    let mut fixture = new();
+    let mut storage = fixture.storage();
+    for (i, key) in KEYS.iter().enumerate() {
+        storage.set_raw(key.to_vec(), vec![i as u8]).unwrap();
+    }
+
+    let prefixes: &[&[u8]] = &[
+        b"",
+        b"\x00",
+        b"a",
+        b"\xfe",
+        b"\xff",
+        b"\xff\xff",
+        b"\xff\xff\xff",
+    ];
+    for prefix in prefixes {
+        for order in [Order::Ascending, Order::Descending] {
+            let mut expected: Vec<_> = KEYS
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| key.starts_with(prefix))
+                .map(|(i, key)| (key.to_vec(), vec![i as u8]))
+                .collect();
+            if order == Order::Descending {
+                expected.reverse();
+            }
+            let actual: Vec<_> = storage
+                .prefix_iter(prefix, order)
+                .unwrap()
+                .map(owned_entry)
+                .collect();
+            assert_eq!(actual, expected, "prefix_iter({prefix:?}, {order:?})");
+
+            let keys: Vec<_> = storage
+                .prefix_keys(prefix, order)
+                .unwrap()
+                .map(|key| key.unwrap().into_owned())
+                .collect();
+            let expected_keys: Vec<_> = expected.into_iter().map(|(key, _)| key).collect();
+            assert_eq!(keys, expected_keys, "prefix_keys({prefix:?}, {order:?})");
+        }
+    }
+}
+
+fn contents<S: IterableStorage>(storage: &S) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let (low, high) = (Bound::<KeyType<()>>::Unbounded, Bound::Unbounded);
+    storage
+        .iter(low, high, Order::Ascending)
+        .unwrap()
+        .map(owned_entry)
+        .collect()
+}
+
+/// Checks range deletes for every combination of `Bound`s, and that a
+/// `WriteBatch` applies its writes in order.
+pub fn write_batch<F: Fixture>(new: impl Fn() -> F) {
+    let bounds = all_bounds(&[b"", b"\x00", b"ab", b"\xff", b"\xff\xff"]);
+    for low in &bounds {
+        for high in &bounds {
+            let mut fixture = new();
+            let mut storage = fixture.storage();
+            let mut reference = BTreeMap::new();
+            for (i, key) in KEYS.iter().enumerate() {
+                storage.set_raw(key.to_vec(), vec![i as u8]).unwrap();
+                reference.insert(key.to_vec(), vec![i as u8]);
+            }
+
+            storage.delete_range_raw(low.clone(), high.clone()).unwrap();
+            let range = (low.clone(), high.clone());
+            reference.retain(|key, _| !range.contains(key));
+            let expected: Vec<_> = reference.into_iter().collect();
+            assert_eq!(
+                contents(&storage),
+                expected,
+                "delete_range({low:?}, {high:?})"
+            );
+        }
+    }
+
+    let mut fixture = new();
+    let mut storage = fixture.storage();
+    storage.set_raw(b"a".to_vec(), b"old".to_vec()).unwrap();
+    storage.set_raw(b"b".to_vec(), b"old".to_vec()).unwrap();
+    let mut batch = WriteBatch::new();
+    batch.put(b"c".to_vec(), b"new".to_vec());
+    batch.delete(b"a".to_vec());
+    batch.delete_range(Bound::Included(b"b".to_vec()), Bound::Unbounded);
+    // Writes after a range delete survive it
+    batch.put(b"d".to_vec(), b"new".to_vec());
+    batch.put(b"a".to_vec(), b"new".to_vec());
+    storage.write_batch(batch).unwrap();
+    assert_eq!(
+        contents(&storage),
+        vec![
+            (b"a".to_vec(), b"new".to_vec()),
+            (b"d".to_vec(), b"new".to_vec()),
+        ]
+    );
+    storage.write_batch(WriteBatch::new()).unwrap();
+    assert_eq!(contents(&storage).len(), 2);
+}
+
+/// Checks the behaviour of every built-in structure on the backend.
+pub fn structures<F: Fixture>(new: impl Fn() -> F) {
+    let mut fixture = new();
+    let mut storage = fixture.storage();
+
+    let item: Item<u64, KeyEncoding> = Item::new(b"item");
+    assert_eq!(item.may_load(&storage), Ok(None));
+    item.save(&mut storage, &42).unwrap();
+    assert_eq!(item.may_load(&storage), Ok(Some(42)));
+    item.delete(&mut storage).unwrap();
+    assert_eq!(item.may_load(&storage), Ok(None));
+
+    // Neighbouring structures on both sides of the map
+    let before: Item<u64, KeyEncoding> = Item::new(b"m");
+    let after: Item<u64, KeyEncoding> = Item::new(b"mapz");
+    before.save(&mut storage, &0).unwrap();
+    after.save(&mut storage, &0).unwrap();
+
+    let map: Map<u8, Map<u8, Item<u64, KeyEncoding>>> = Map::new(b"map");
+    for outer in [0, 1, 0xff] {
+        for inner in [0, 0xff] {
+            let value = u64::from(outer) << 8 | u64::from(inner);
+            let item = map.at(outer).unwrap().at(inner).unwrap();
+            item.save(&mut storage, &value).unwrap();
+        }
+    }
+    let collect = |map: &Map<u8, Item<u64, KeyEncoding>>, start, end, order| {
+        map.range(&storage, start, end, order)
+            .unwrap()
+            .map(|res| res.unwrap().1)
+            .collect::<Vec<_>>()
+    };
+    let inner = map.at(0xff).unwrap();
+    let all = collect(
+        &inner,
+        Bound::Unbounded,
+        Bound::Unbounded,
+        Order::Descending,
+    );
+    assert_eq!(all, vec![0xffff, 0xff00]);
+    let some = collect(
+        &inner,
+        Bound::Excluded(0),
+        Bound::Unbounded,
+        Order::Ascending,
+    );
+    assert_eq!(some, vec![0xffff]);
+    let values: Vec<_> = map
+        .range(
+            &storage,
+            Bound::Unbounded,
+            Bound::Unbounded,
+            Order::Descending,
+        )
+        .unwrap()
+        .map(|res| res.unwrap().1)
+        .collect();
+    assert_eq!(values, vec![0xffff, 0xff00, 0x01ff, 0x0100, 0x00ff, 0x0000]);
+
+    let vector: Vector<Item<u64, KeyEncoding>> = Vector::new(b"vector");
+    for i in 0..5 {
+        assert_eq!(vector.push(&mut storage, &i), Ok(i as usize));
+    }
+    assert_eq!(vector.swap_remove(&mut storage, 0), Ok(0));
+    assert_eq!(vector.pop(&mut storage), Ok(Some(3)));
+    let values: Vec<_> = vector
+        .iter(&storage, Order::Ascending)
+        .unwrap()
+        .map(|res| res.map(|((index, _), value)| (index, value)).unwrap())
+        .collect();
+    assert_eq!(values, vec![(0, 4), (1, 1), (2, 2)]);
+    vector.clear(&mut storage).unwrap();
+    assert_eq!(vector.len(&storage), Ok(0));
+
+    let pq: PriorityQueue<i32, u64, KeyEncoding> = PriorityQueue::new(b"pq");
+    for priority in [3, -1, 2] {
+        pq.push(&mut storage, priority, &(priority as u64)).unwrap();
+    }
+    assert_eq!(pq.peek(&storage, Order::Descending), Ok(Some((3, 3))));
+    assert_eq!(
+        pq.pop(&mut storage, Order::Ascending),
+        Ok(Some((-1, u64::MAX)))
+    );
+    assert_eq!(pq.pop(&mut storage, Order::Descending), Ok(Some((3, 3))));
+    assert_eq!(pq.pop(&mut storage, Order::Descending), Ok(Some((2, 2))));
+    assert_eq!(pq.pop(&mut storage, Order::Descending), Ok(None));
+
+    let mpq: MultiPriorityQueue<u8, u64, KeyEncoding> = MultiPriorityQueue::new(b"mpq");
+    for (priority, value) in [(1, 10), (2, 20), (1, 11), (2, 21)] {
+        mpq.push(&mut storage, priority, &value).unwrap();
+    }
+    assert_eq!(mpq.len(&storage), Ok(4));
+    assert_eq!(mpq.pop(&mut storage, Order::Descending), Ok(Some((2, 20))));
+    assert_eq!(mpq.pop(&mut storage, Order::Ascending), Ok(Some((1, 10))));
+    assert_eq!(mpq.pop_all_at(&mut storage, 1), Ok(vec![11]));
+    assert_eq!(mpq.pop(&mut storage, Order::Ascending), Ok(Some((2, 21))));
+    assert_eq!(mpq.is_empty(&storage), Ok(true));
+
+    assert_eq!(before.may_load(&storage), Ok(Some(0)));
+    assert_eq!(after.may_load(&storage), Ok(Some(0)));
+}
+
+/// Checks a [`Transaction`] over the backend: merged reads and iteration,
+/// rollback, and commit.
+pub fn transaction<F: Fixture>(new: impl Fn() -> F) {
+    let mut fixture = new();
+    let mut storage = fixture.storage();
+    let mut reference = BTreeMap::new();
+    for (i, key) in KEYS.iter().enumerate().filter(|(i, _)| i % 2 == 0) {
+        storage.set_raw(key.to_vec(), vec![i as u8]).unwrap();
+        reference.insert(key.to_vec(), vec![i as u8]);
+    }
+
+    let mut tx = Transaction::new(&mut storage);
+    tx.set_raw(b"a".to_vec(), b"inserted".to_vec()).unwrap();
+    tx.set_raw(b"".to_vec(), b"overwritten".to_vec()).unwrap();
+    tx.delete_raw(b"\xff\xff").unwrap();
+    reference.insert(b"a".to_vec(), b"inserted".to_vec());
+    reference.insert(b"".to_vec(), b"overwritten".to_vec());
+    reference.remove(b"\xff\xff".as_slice());
+
+    let bounds = all_bounds(&[b"", b"\x01", b"a", b"\xff\xff"]);
+    for low in &bounds {
+        for high in &bounds {
+            for order in [Order::Ascending, Order::Descending] {
+                let expected = expected_range(&reference, low, high, order);
+                let actual: Vec<_> = tx
+                    .iter(raw_bound(low), raw_bound(high), order)
+                    .unwrap()
+                    .map(owned_entry)
+                    .collect();
+                assert_eq!(actual, expected, "iter({low:?}, {high:?}, {order:?})");
+            }
+        }
+    }
+
+    tx.rollback();
+    assert_eq!(get(&storage, b"a"), None);
+    assert_eq!(get(&storage, b"\xff\xff"), Some(vec![10]));
+
+    let mut tx = Transaction::new(&mut storage);
+    tx.set_raw(b"a".to_vec(), b"inserted".to_vec()).unwrap();
+    tx.delete_raw(b"\xff\xff").unwrap();
+    tx.commit().unwrap();
+    assert_eq!(get(&storage, b"a"), Some(b"inserted".to_vec()));
+    assert_eq!(get(&storage, b"\xff\xff"), None);
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Set(Vec<u8>, Vec<u8>),
+    Delete(Vec<u8>),
+    DeleteRange(Bound<Vec<u8>>, Bound<Vec<u8>>),
+    Get(Vec<u8>),
+    Iter(Bound<Vec<u8>>, Bound<Vec<u8>>, Order),
+}
+
+fn key_strategy() -> impl Strategy<Value = Vec<u8>> {
+    // A small alphabet makes collisions and shared prefixes likely
+    vec(
+        prop_oneof![Just(0x00), Just(0x01), Just(0x7f), Just(0xff)],
+        0..4,
+    )
+}
+
+fn bound_strategy() -> impl Strategy<Value = Bound<Vec<u8>>> {
+    prop_oneof![
+        Just(Bound::Unbounded),
+        key_strategy().prop_map(Bound::Included),
+        key_strategy().prop_map(Bound::Excluded),
+    ]
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    let order = prop_oneof![Just(Order::Ascending), Just(Order::Descending)];
+    prop_oneof![
+        3 => (key_strategy(), vec(any::<u8>(), 0..4)).prop_map(|(k, v)| Op::Set(k, v)),
+        1 => key_strategy().prop_map(Op::Delete),
+        1 => (bound_strategy(), bound_strategy()).prop_map(|(l, h)| Op::DeleteRange(l, h)),
+        1 => key_strategy().prop_map(Op::Get),
+        2 => (bound_strategy(), bound_strategy(), order).prop_map(|(l, h, o)| Op::Iter(l, h, o)),
+    ]
+}
+
+/// Property test: applies random sequences of operations to the backend and to
+/// the `BTreeMap` reference implementation, and checks every read agrees.
+pub fn matches_reference<F: Fixture>(new: impl Fn() -> F) {
+    let mut runner = TestRunner::default();
+    runner
+        .run(&vec(op_strategy(), 0..64), |ops| {
+            let mut fixture = new();
+            let mut storage = fixture.storage();
+            let mut reference: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+            for op in ops {
+                match op {
+                    Op::Set(key, value) => {
+                        storage.set_raw(key.clone(), value.clone()).unwrap();
+                        reference.insert(key, value);
+                    }
+                    Op::Delete(key) => {
+                        storage.delete_raw(&key).unwrap();
+                        reference.remove(&key);
+                    }
+                    Op::DeleteRange(low, high) => {
+                        storage.delete_range_raw(low.clone(), high.clone()).unwrap();
+                        let range = (low, high);
+                        reference.retain(|key, _| !range.contains(key));
+                    }
+                    Op::Get(key) => {
+                        prop_assert_eq!(get(&storage, &key), reference.get(&key).cloned());
+                    }
+                    Op::Iter(low, high, order) => {
+                        let expected = expected_range(&reference, &low, &high, order);
+                        let actual: Vec<_> = storage
+                            .iter(raw_bound(&low), raw_bound(&high), order)
+                            .unwrap()
+                            .map(owned_entry)
+                            .collect();
+                        prop_assert_eq!(actual, expected);
+                    }
+                }
+            }
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    mod btree_map {
+        crate::storage_conformance_tests!(std::collections::BTreeMap::new);
+    }
+
+    mod transaction {
+        use std::collections::BTreeMap;
+
+        use crate::{testing::Fixture, Transaction};
+
+        /// A transaction over a map it owns.
+        #[derive(Default)]
+        struct TransactionFixture(BTreeMap<Vec<u8>, Vec<u8>>);
+
+        impl Fixture for TransactionFixture {
+            type Storage<'a> = Transaction<'a, BTreeMap<Vec<u8>, Vec<u8>>>;
+
+            fn storage(&mut self) -> Self::Storage<'_> {
+                Transaction::new(&mut self.0)
+            }
+        }
+
+        crate::storage_conformance_tests!(TransactionFixture::default);
+    }
+}
@@ -0,0 +1,548 @@
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{btree_map, BTreeMap},
+    iter::Peekable,
+    ops::Bound,
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
+};
+
+use crate::{
+    storage::{encode_bound, is_empty_range, key_of, scan_delete_range},
+    BackendError, Directed, Encodable, IterableStorage, KeyEncoding, KeyResult, KeyType, KvResult,
+    Order, RawStorageError, Storage, StorageMut, WriteBatch,
+};
+
+/// A buffered overlay over a storage backend.
+///
+/// Writes made through the transaction are kept in memory, and are only applied
+/// to the underlying storage on [`Transaction::commit`]. Reads and iteration see
+/// the buffered writes merged with the underlying storage. Dropping the
+/// transaction, or calling [`Transaction::rollback`], discards the buffered writes.
+///
+/// Savepoints allow undoing part of the buffered writes without discarding the
+/// whole transaction. They nest to any depth: see [`Transaction::savepoint`].
+pub struct Transaction<'s, S: StorageMut + IterableStorage> {
+    storage: &'s mut S,
+    /// Buffered writes, where `None` marks a deleted key.
+    pending: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    /// Previous buffered state of each key written while a savepoint is active.
+    undo: Vec<UndoEntry>,
+    /// Generation and length of the undo log when each active savepoint was
+    /// created.
+    savepoints: Vec<(u64, usize)>,
+    /// Identifies this transaction's savepoints among those of others.
+    id: u64,
+    /// Generation of the next savepoint, so that a stale handle never matches a
+    /// newer savepoint at the same depth.
+    next_generation: u64,
+}
+
+/// Source of [`Transaction`] ids.
+static NEXT_TRANSACTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A key and its previous buffered state, where `None` marks a key that was not
+/// buffered.
+type UndoEntry = (Vec<u8>, Option<Option<Vec<u8>>>);
+
+/// A handle to a point in a [`Transaction`] that can be rolled back to or
+/// released. Obtained from [`Transaction::savepoint`].
+#[derive(Debug, PartialEq, Eq)]
+#[must_use]
+pub struct Savepoint {
+    transaction: u64,
+    generation: u64,
+    depth: usize,
+}
+
+impl<'s, S: StorageMut + IterableStorage> Transaction<'s, S> {
+    pub fn new(storage: &'s mut S) -> Self {
+        Self {
+            storage,
+            pending: BTreeMap::new(),
+            undo: Vec::new(),
+            savepoints: Vec::new(),
+            id: NEXT_TRANSACTION_ID.fetch_add(1, AtomicOrdering::Relaxed),
+            next_generation: 0,
+        }
+    }
+
+    /// Applies all buffered writes to the underlying storage, as a single
+    /// [`WriteBatch`].
+    ///
+    /// The commit is atomic if the storage applies batches atomically. Otherwise,
+    /// if the storage fails, the writes applied before the failure are kept, and
+    /// the rest are discarded.
+    pub fn commit(self) -> Result<(), BackendError> {
+        let mut batch = WriteBatch::new();
+        for (key, value) in self.pending {
+            match value {
+                Some(value) => batch.put(key, value),
+                None => batch.delete(key),
+            }
+        }
+        self.storage.write_batch(batch)
+    }
+
+    /// Discards all buffered writes. Equivalent to dropping the transaction.
+    pub fn rollback(self) {}
+
+    /// Creates a savepoint, nested inside any savepoint that is still active.
+    pub fn savepoint(&mut self) -> Savepoint {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.savepoints.push((generation, self.undo.len()));
+        Savepoint {
+            transaction: self.id,
+            generation,
+            depth: self.savepoints.len() - 1,
+        }
+    }
+
+    /// Discards all writes made since `savepoint` was created. The savepoint, and
+    /// any savepoints nested inside it, are no longer active.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `savepoint` belongs to another transaction, or is no longer
+    /// active because it or a savepoint it is nested in was already released or
+    /// rolled back.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        let len = self.deactivate(savepoint);
+        for (key, value) in self.undo.drain(len..).rev() {
+            match value {
+                Some(value) => self.pending.insert(key, value),
+                None => self.pending.remove(&key),
+            };
+        }
+    }
+
+    /// Keeps all writes made since `savepoint` was created, merging them into the
+    /// enclosing savepoint or the transaction itself. The savepoint, and any
+    /// savepoints nested inside it, are no longer active.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `savepoint` belongs to another transaction, or is no longer
+    /// active because it or a savepoint it is nested in was already released or
+    /// rolled back.
+    pub fn release(&mut self, savepoint: Savepoint) {
+        self.deactivate(savepoint);
+        if self.savepoints.is_empty() {
+            self.undo.clear();
+        }
+    }
+
+    /// Removes `savepoint` and all savepoints nested inside it from the stack,
+    /// returning the length of the undo log when it was created.
+    fn deactivate(&mut self, savepoint: Savepoint) -> usize {
+        assert!(
+            savepoint.transaction == self.id,
+            "savepoint belongs to another transaction"
+        );
+        let active = self.savepoints.get(savepoint.depth);
+        let len = match active {
+            Some(&(generation, len)) if generation == savepoint.generation => len,
+            _ => panic!("savepoint was already released or rolled back"),
+        };
+        self.savepoints.truncate(savepoint.depth);
+        len
+    }
+
+    fn write(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+        let previous = self.pending.insert(key.clone(), value);
+        if !self.savepoints.is_empty() {
+            self.undo.push((key, previous));
+        }
+    }
+}
+
+impl<S: StorageMut + IterableStorage> Storage for Transaction<'_, S> {
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, BackendError> {
+        match self.pending.get(key) {
+            Some(value) => Ok(value.as_deref().map(Cow::Borrowed)),
+            None => self.storage.get_raw(key),
+        }
+    }
+}
+
+impl<S: StorageMut + IterableStorage> StorageMut for Transaction<'_, S> {
+    fn set_raw(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<(), BackendError> {
+        self.write(key, Some(value));
+        Ok(())
+    }
+
+    fn delete_raw(&mut self, key: &[u8]) -> Result<(), BackendError> {
+        self.write(key.to_vec(), None);
+        Ok(())
+    }
+
+    /// Buffers a delete of every key currently in the range, whether buffered or
+    /// in the underlying storage.
+    fn delete_range_raw(
+        &mut self,
+        low: Bound<Vec<u8>>,
+        high: Bound<Vec<u8>>,
+    ) -> Result<(), BackendError> {
+        scan_delete_range(self, low, high)
+    }
+}
+
+impl<S: StorageMut + IterableStorage> IterableStorage for Transaction<'_, S> {
+    type Keys<'a>
+        = std::iter::Map<TransactionIter<'a, S::Iter<'a>>, fn(KvResult<'a>) -> KeyResult<'a>>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = TransactionIter<'a, S::Iter<'a>>
+    where
+        Self: 'a;
+
+    fn keys<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Keys<'_>, RawStorageError> {
+        Ok(self.iter(low, high, order)?.map(key_of as fn(_) -> _))
+    }
+
+    fn iter<K: Encodable<KeyEncoding>>(
+        &self,
+        low: Bound<K>,
+        high: Bound<K>,
+        order: Order,
+    ) -> Result<Self::Iter<'_>, RawStorageError> {
+        let low = encode_bound!(low);
+        let high = encode_bound!(high);
+        // BTreeMap::range panics if low > high or low == high, with Bound::Excluded
+        let pending = match is_empty_range(&low, &high) {
+            true => Directed::empty(order),
+            false => Directed::new(self.pending.range((low.clone(), high.clone())), order),
+        };
+        let storage = self.storage.iter(raw_bound(low), raw_bound(high), order)?;
+
+        Ok(TransactionIter {
+            storage: storage.peekable(),
+            pending: pending.peekable(),
+            order,
+        })
+    }
+}
+
+fn raw_bound(bound: Bound<Vec<u8>>) -> Bound<KeyType<()>> {
+    bound.map(KeyType::Raw)
+}
+
+/// Buffered writes, where a `None` value marks a deleted key.
+type PendingRange<'a> = Directed<btree_map::Range<'a, Vec<u8>, Option<Vec<u8>>>>;
+
+/// Iterator over a [`Transaction`], merging the underlying storage iterator `I`
+/// with the buffered writes. On equal keys, the buffered write takes precedence.
+/// Storage errors are yielded as soon as they are seen.
+pub struct TransactionIter<'a, I: Iterator<Item = KvResult<'a>>> {
+    storage: Peekable<I>,
+    pending: Peekable<PendingRange<'a>>,
+    order: Order,
+}
+
+impl<'a, I: Iterator<Item = KvResult<'a>>> Iterator for TransactionIter<'a, I> {
+    type Item = KvResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ordering = match (self.storage.peek(), self.pending.peek()) {
+                (None, None) => return None,
+                (Some(Err(_)), _) | (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(Ok((stored, _))), Some((pending, _))) => match self.order {
+                    Order::Ascending => stored.as_ref().cmp(pending.as_slice()),
+                    Order::Descending => pending.as_slice().cmp(stored.as_ref()),
+                },
+            };
+
+            match ordering {
+                Ordering::Less => return self.storage.next(),
+                Ordering::Equal => {
+                    self.storage.next();
+                }
+                Ordering::Greater => {}
+            }
+
+            let (key, value) = self.pending.next()?;
+            if let Some(value) = value {
+                return Some(Ok((Cow::Borrowed(key), Cow::Borrowed(value))));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        mock::{DisplayEncoding, FailingStorage},
+        Item, Map, StorageError,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_transaction() {
+        const ITEM: Item<String, DisplayEncoding> = Item::new(b"foo");
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        ITEM.save(&mut storage, &"bar".to_string()).unwrap();
+
+        let mut tx = Transaction::new(&mut storage);
+        assert_eq!(ITEM.may_load(&tx), Ok(Some("bar".to_string())));
+        ITEM.save(&mut tx, &"baz".to_string()).unwrap();
+        assert_eq!(ITEM.may_load(&tx), Ok(Some("baz".to_string())));
+        tx.rollback();
+        assert_eq!(ITEM.may_load(&storage), Ok(Some("bar".to_string())));
+
+        let mut tx = Transaction::new(&mut storage);
+        ITEM.delete(&mut tx).unwrap();
+        assert_eq!(ITEM.may_load(&tx), Ok(None));
+        tx.commit().unwrap();
+        assert_eq!(ITEM.may_load(&storage), Ok(None));
+    }
+
+    #[test]
+    fn test_transaction_iter() {
+        const MAP: Map<u8, Item<u8, DisplayEncoding>> = Map::new(b"foo");
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for i in [1, 3, 5, 7] {
+            MAP.at(i).unwrap().save(&mut storage, &i).unwrap();
+        }
+
+        let mut tx = Transaction::new(&mut storage);
+        MAP.at(0).unwrap().save(&mut tx, &0).unwrap();
+        MAP.at(3).unwrap().save(&mut tx, &33).unwrap();
+        MAP.at(4).unwrap().save(&mut tx, &4).unwrap();
+        MAP.at(5).unwrap().delete(&mut tx).unwrap();
+        MAP.at(8).unwrap().save(&mut tx, &8).unwrap();
+
+        let collect = |tx: &Transaction<_>, low, high, order| {
+            MAP.range(tx, low, high, order)
+                .unwrap()
+                .map(|res| res.map(|(k, v)| (k.0, v)))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        let expected = vec![(0, 0), (1, 1), (3, 33), (4, 4), (7, 7), (8, 8)];
+        let all = collect(&tx, Bound::Unbounded, Bound::Unbounded, Order::Ascending);
+        assert_eq!(all, expected);
+        let all = collect(&tx, Bound::Unbounded, Bound::Unbounded, Order::Descending);
+        assert_eq!(all, expected.into_iter().rev().collect::<Vec<_>>());
+
+        let some = collect(
+            &tx,
+            Bound::Excluded(1),
+            Bound::Included(5),
+            Order::Ascending,
+        );
+        assert_eq!(some, vec![(3, 33), (4, 4)]);
+        let some = collect(
+            &tx,
+            Bound::Included(4),
+            Bound::Excluded(8),
+            Order::Descending,
+        );
+        assert_eq!(some, vec![(7, 7), (4, 4)]);
+        let none = collect(
+            &tx,
+            Bound::Excluded(4),
+            Bound::Excluded(4),
+            Order::Ascending,
+        );
+        assert_eq!(none, vec![]);
+
+        tx.commit().unwrap();
+        assert_eq!(storage.len(), 6);
+    }
+
+    #[test]
+    fn test_transaction_error_discards_writes() {
+        const A: Item<String, DisplayEncoding> = Item::new(b"a");
+        const B: Item<u32, DisplayEncoding> = Item::new(b"b");
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+
+        fn handler<S: StorageMut + IterableStorage>(
+            storage: &mut S,
+        ) -> Result<(), StorageError<DisplayEncoding>> {
+            A.save(storage, &"written".to_string())?;
+            Err(StorageError::IndexOutOfBounds(1, 0))?;
+            B.save(storage, &1)
+        }
+
+        let mut tx = Transaction::new(&mut storage);
+        assert!(handler(&mut tx).is_err());
+        drop(tx);
+        assert!(storage.is_empty());
+
+        let mut tx = Transaction::new(&mut storage);
+        A.save(&mut tx, &"written".to_string()).unwrap();
+        B.save(&mut tx, &1).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(A.may_load(&storage), Ok(Some("written".to_string())));
+        assert_eq!(B.may_load(&storage), Ok(Some(1)));
+    }
+
+    #[test]
+    fn test_transaction_backend_error() {
+        let mut storage = FailingStorage::default();
+        storage.set_raw(b"a".to_vec(), b"1".to_vec()).unwrap();
+        storage.set_raw(b"c".to_vec(), b"3".to_vec()).unwrap();
+        storage.failing = true;
+
+        let mut tx = Transaction::new(&mut storage);
+        tx.set_raw(b"b".to_vec(), b"2".to_vec()).unwrap();
+        let error = || BackendError::new("disk on fire");
+        assert_eq!(tx.get_raw(b"b"), Ok(Some(Cow::Borrowed(b"2".as_slice()))));
+        assert_eq!(tx.get_raw(b"a"), Err(error()));
+        let entries: Vec<_> = tx
+            .iter::<KeyType<()>>(Bound::Unbounded, Bound::Unbounded, Order::Ascending)
+            .unwrap()
+            .take(2)
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                Ok((b"a".as_slice().into(), b"1".as_slice().into())),
+                Err(error())
+            ]
+        );
+        assert_eq!(tx.commit(), Err(error()));
+    }
+
+    #[test]
+    fn test_savepoints() {
+        const ITEM: Item<u32, DisplayEncoding> = Item::new(b"foo");
+        const MAP: Map<u8, Item<u8, DisplayEncoding>> = Map::new(b"bar");
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        ITEM.save(&mut storage, &0).unwrap();
+
+        let mut tx = Transaction::new(&mut storage);
+        ITEM.save(&mut tx, &1).unwrap();
+
+        let outer = tx.savepoint();
+        ITEM.save(&mut tx, &2).unwrap();
+        MAP.at(1).unwrap().save(&mut tx, &1).unwrap();
+
+        let inner = tx.savepoint();
+        ITEM.delete(&mut tx).unwrap();
+        MAP.at(1).unwrap().save(&mut tx, &11).unwrap();
+        MAP.at(2).unwrap().save(&mut tx, &2).unwrap();
+
+        let innermost = tx.savepoint();
+        MAP.at(3).unwrap().save(&mut tx, &3).unwrap();
+        tx.release(innermost);
+        assert_eq!(MAP.at(3).unwrap().may_load(&tx), Ok(Some(3)));
+
+        tx.rollback_to(inner);
+        assert_eq!(ITEM.may_load(&tx), Ok(Some(2)));
+        assert_eq!(MAP.at(1).unwrap().may_load(&tx), Ok(Some(1)));
+        assert_eq!(MAP.at(2).unwrap().may_load(&tx), Ok(None));
+        assert_eq!(MAP.at(3).unwrap().may_load(&tx), Ok(None));
+
+        tx.rollback_to(outer);
+        assert_eq!(ITEM.may_load(&tx), Ok(Some(1)));
+        assert_eq!(MAP.at(1).unwrap().may_load(&tx), Ok(None));
+
+        tx.commit().unwrap();
+        assert_eq!(ITEM.may_load(&storage), Ok(Some(1)));
+        assert_eq!(storage.len(), 1);
+    }
+
+    #[test]
+    fn test_savepoint_batch() {
+        use crate::{DataStructure, PriorityQueue};
+
+        let pq: PriorityQueue<u8, String, DisplayEncoding> = PriorityQueue::new(b"pq");
+        let done: Item<u32, DisplayEncoding> = Item::with_prefix(b"zz".to_vec());
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        for i in 0..4 {
+            pq.push(&mut storage, i, &format!("job-{i}")).unwrap();
+        }
+
+        // Process jobs until the first failure, keeping the work done so far.
+        let mut tx = Transaction::new(&mut storage);
+        loop {
+            let savepoint = tx.savepoint();
+            let (priority, _) = pq.pop(&mut tx, Order::Ascending).unwrap().unwrap();
+            if priority == 2 {
+                tx.rollback_to(savepoint);
+                break;
+            }
+            let count = done.may_load(&tx).unwrap().unwrap_or(0);
+            done.save(&mut tx, &(count + 1)).unwrap();
+            tx.release(savepoint);
+        }
+        tx.commit().unwrap();
+
+        assert_eq!(done.may_load(&storage), Ok(Some(2)));
+        assert_eq!(
+            pq.peek(&storage, Order::Ascending).unwrap(),
+            Some((2, "job-2".to_string()))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "savepoint was already released or rolled back")]
+    fn test_stale_savepoint() {
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let mut tx = Transaction::new(&mut storage);
+        let outer = tx.savepoint();
+        let inner = tx.savepoint();
+        tx.rollback_to(outer);
+        tx.release(inner);
+    }
+
+    #[test]
+    #[should_panic(expected = "savepoint was already released or rolled back")]
+    fn test_stale_savepoint_after_release() {
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let mut tx = Transaction::new(&mut storage);
+        let outer = tx.savepoint();
+        let inner = tx.savepoint();
+        tx.release(outer);
+
+        // `inner` must not act on the newer savepoint at its depth
+        let _outer = tx.savepoint();
+        let _newer = tx.savepoint();
+        tx.rollback_to(inner);
+    }
+
+    #[test]
+    fn test_stale_savepoint_after_rollback_to() {
+        const ITEM: Item<u32, DisplayEncoding> = Item::new(b"foo");
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let mut tx = Transaction::new(&mut storage);
+        let outer = tx.savepoint();
+        let inner = tx.savepoint();
+        tx.rollback_to(outer);
+
+        let _outer = tx.savepoint();
+        let _newer = tx.savepoint();
+        ITEM.save(&mut tx, &1).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            tx.rollback_to(inner);
+        }));
+        assert!(result.is_err());
+        assert_eq!(ITEM.may_load(&tx), Ok(Some(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "savepoint belongs to another transaction")]
+    fn test_savepoint_of_other_transaction() {
+        let mut storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let mut other_storage: BTreeMap<Vec<u8>, Vec<u8>> = BTreeMap::new();
+        let mut tx = Transaction::new(&mut storage);
+        let mut other = Transaction::new(&mut other_storage);
+        let _savepoint = tx.savepoint();
+        let foreign = other.savepoint();
+        tx.release(foreign);
+    }
+}